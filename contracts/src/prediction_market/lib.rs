@@ -1,4 +1,5 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
+mod math;
 mod types;
 use crate::types::*;
 use ink::prelude::string::String;
@@ -28,10 +29,87 @@ pub enum Error {
     MoreThanOneSupply,
     NotEnoughBalance,
     TraderNotIdentitied,
+    InvalidPricing,
+    MathOverflow,
+    TransferFailed,
+    NotEnoughSupply,
+    MarketNotCancelled,
+    NothingToClaim,
+    NotProposed,
+    NotDisputed,
+    DisputeWindowClosed,
+    DisputeWindowOpen,
+    InvalidOracle,
+    ResolverMismatch,
+    OracleCallFailed,
+    OracleNotReady,
+    InvalidCommittee,
+    NotCommitteeMember,
+    OrderNotFound,
+    InvalidOrder,
+    InvalidAuction,
+    BidTooLow,
+    FundDissolved,
+    NotShareholder,
+    GovernanceNotFound,
+    AlreadyVoted,
+    VotingClosed,
+    VotingOpen,
+    InvalidFee,
+    CollateralNotAllowed,
+    CollateralMismatch,
+    InvalidScalar,
+    SubscriptionClosed,
+    SubscriptionCapReached,
+    ZeroNetAssetValue,
+    OrderBookFull,
+    ZeroAmount,
+    MarketNotOpen,
+    SellNotSupported,
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP37Error {
+    Custom(String),
+    InsufficientBalance,
+    TransferToZeroAddress,
+    NotAllowed,
+    SafeTransferCheckFailed(String),
+}
+
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP22Error {
+    Custom(String),
+    InsufficientBalance,
+    InsufficientAllowance,
+    ZeroRecipientAddress,
+    ZeroSenderAddress,
+    SafeTransferCheckFailed(String),
+}
+
+impl From<Error> for PSP22Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NotEnoughShare => PSP22Error::InsufficientBalance,
+            error => PSP22Error::Custom(ink::prelude::format!("{:?}", error)),
+        }
+    }
+}
+
+impl From<Error> for PSP37Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NotEnoughSupply => PSP37Error::InsufficientBalance,
+            Error::NotOwner => PSP37Error::NotAllowed,
+            error => PSP37Error::Custom(ink::prelude::format!("{:?}", error)),
+        }
+    }
+}
+
 #[ink::trait_definition]
 pub trait EventCore {
     #[ink(message, payable)]
@@ -41,10 +119,30 @@ pub trait EventCore {
         resolve_date: Timestamp,
         bets: Vec<OutComePayload>,
         metadata: EventMetadata,
+        terms: MarketTerms,
+        resolver: Option<ResolverPayload>,
     ) -> Result<EventId>;
 
     #[ink(message)]
-    fn resolve_event(&mut self, event_id: EventId, winner: OutComeId) -> Result<()>;
+    fn cancel_event(&mut self, event_id: EventId) -> Result<()>;
+
+    #[ink(message)]
+    fn claim_refund(&mut self, event_id: EventId, fund_id: InvestmentFundId) -> Result<Balance>;
+
+    #[ink(message)]
+    fn reclaim_deposit(&mut self, event_id: EventId) -> Result<Balance>;
+
+    #[ink(message)]
+    fn set_admin(&mut self, admin: AccountId) -> Result<()>;
+
+    #[ink(message, payable)]
+    fn mint_complete_set(&mut self, event_id: EventId, amount: Supply) -> Result<Balance>;
+
+    #[ink(message)]
+    fn redeem_complete_set(&mut self, event_id: EventId, amount: Supply) -> Result<Balance>;
+
+    #[ink(message)]
+    fn quote_bet(&self, outcome_id: OutComeId, supplies: Supply) -> Result<BetQuote>;
 
     #[ink(message)]
     fn get_events(&self) -> Result<Vec<(Event, EventMarket, Supply)>>;
@@ -68,6 +166,8 @@ pub trait FundCore {
         &mut self,
         total_share: Share,
         metadata: FundMetadata,
+        collateral: Option<AccountId>,
+        deposit: Balance,
     ) -> Result<InvestmentFundId>;
 
     #[ink(message)]
@@ -86,719 +186,7667 @@ pub trait FundCore {
         supplies: Supply,
     ) -> Result<()>;
 
+    #[ink(message)]
+    fn sell(
+        &mut self,
+        outcome_id: OutComeId,
+        fund_id: InvestmentFundId,
+        supplies: Supply,
+    ) -> Result<Balance>;
+
     #[ink(message)]
     fn create_proposal(
         &mut self,
         fund_id: InvestmentFundId,
         amount: Share,
-        price: Balance,
+        price_per_share: Balance,
+        min_fill: Share,
         duration: Option<Timestamp>,
         proposed_person: Option<AccountId>,
     ) -> Result<TradeId>;
 
     #[ink(message, payable)]
-    fn accept_proposal(&mut self, trade_id: TradeId) -> Result<()>;
+    fn accept_proposal(&mut self, trade_id: TradeId, shares: Share) -> Result<()>;
+
+    #[ink(message)]
+    fn cancel_proposal(&mut self, trade_id: TradeId) -> Result<()>;
+
+    #[ink(message)]
+    fn update_proposal(
+        &mut self,
+        trade_id: TradeId,
+        price_per_share: Balance,
+        close_time: Timestamp,
+        proposed_person: Option<AccountId>,
+    ) -> Result<()>;
+
+    #[ink(message)]
+    fn create_auction(
+        &mut self,
+        fund_id: InvestmentFundId,
+        amount: Share,
+        start_price: Balance,
+        duration: Option<Timestamp>,
+        auction: AuctionMode,
+    ) -> Result<TradeId>;
+
+    #[ink(message, payable)]
+    fn bid_auction(&mut self, trade_id: TradeId, price_per_share: Balance) -> Result<()>;
+
+    #[ink(message)]
+    fn settle_auction(&mut self, trade_id: TradeId) -> Result<()>;
+
+    #[ink(message)]
+    fn redeem(&mut self, fund_id: InvestmentFundId, shares: Share) -> Result<Balance>;
+
+    #[ink(message, payable)]
+    fn subscribe(&mut self, fund_id: InvestmentFundId, deposit: Balance) -> Result<Share>;
+
+    #[ink(message)]
+    fn set_subscription(
+        &mut self,
+        fund_id: InvestmentFundId,
+        subscription: SubscriptionConfig,
+    ) -> Result<()>;
+
+    #[ink(message)]
+    fn set_fees(&mut self, fund_id: InvestmentFundId, fees: FundFees) -> Result<()>;
+
+    #[ink(message)]
+    fn collect_fees(&mut self, fund_id: InvestmentFundId) -> Result<Balance>;
+
+    #[ink(message)]
+    fn set_fee_limits(&mut self, max_management_fee: u32, max_performance_fee: u32) -> Result<()>;
+
+    #[ink(message)]
+    fn get_fee_limits(&self) -> Result<(u32, u32)>;
+
+    #[ink(message)]
+    fn set_distribution_policy(
+        &mut self,
+        fund_id: InvestmentFundId,
+        distribution: DistributionPolicy,
+    ) -> Result<()>;
+
+    #[ink(message)]
+    fn claim_dividends(&mut self, fund_id: InvestmentFundId) -> Result<Balance>;
+
+    #[ink(message)]
+    fn get_dividends(&self, fund_id: InvestmentFundId, owner: AccountId) -> Result<Balance>;
+
+    #[ink(message)]
+    fn claim_redemption(&mut self, outcome_id: OutComeId) -> Result<Balance>;
+
+    #[ink(message)]
+    fn get_redemption_claims(&self, owner: AccountId) -> Result<Vec<(OutCome, Position)>>;
 
     #[ink(message)]
     fn get_funds(
         &self,
         owner: Option<AccountId>,
-    ) -> Result<Vec<(InvestmentFund, Vec<(OutCome, Supply)>, Option<Share>)>>;
+    ) -> Result<
+        Vec<(
+            InvestmentFund,
+            Vec<(OutCome, Supply)>,
+            Option<Share>,
+            Balance,
+        )>,
+    >;
+
+    #[ink(message)]
+    fn get_fund_nav(&self, fund_id: InvestmentFundId) -> Result<FundValuation>;
 
     #[ink(message)]
     fn get_owner_share(&self, fund_id: InvestmentFundId, owner: AccountId) -> Result<Share>;
 
+    #[ink(message)]
+    fn get_share_at(
+        &self,
+        fund_id: InvestmentFundId,
+        owner: AccountId,
+        block: BlockNumber,
+    ) -> Result<Share>;
+
+    #[ink(message)]
+    fn get_total_share_at(&self, fund_id: InvestmentFundId, block: BlockNumber) -> Result<Share>;
+
     #[ink(message)]
     fn get_proposals(
         &self,
         proponent: Option<AccountId>,
+        status: Option<TradeStatus>,
     ) -> Result<Vec<(InvestmentFund, FundTrade)>>;
 
     #[ink(message)]
     fn get_fund_proposals(
         &self,
         fund_id: InvestmentFundId,
+        status: Option<TradeStatus>,
     ) -> Result<(InvestmentFund, Vec<FundTrade>)>;
-}
 
-impl ink::env::Environment for MyEnvironment {
-    const MAX_EVENT_TOPICS: usize = 3;
-    type AccountId = AccountId;
-    type Balance = Balance;
-    type Hash = Hash;
-    type Timestamp = Timestamp;
-    type BlockNumber = BlockNumber;
-    type ChainExtension = ink::env::NoChainExtension;
+    #[ink(message)]
+    fn get_trade_price(&self, trade_id: TradeId) -> Result<Balance>;
+
+    #[ink(message)]
+    fn get_trade_fills(&self, trade_id: TradeId) -> Result<Vec<TradeFill>>;
 }
 
-#[ink::contract(env = MyEnvironment)]
-mod prediction_market {
-    use crate::*;
+#[ink::trait_definition]
+pub trait OrderBookCore {
+    #[ink(message, payable)]
+    fn place_bid(
+        &mut self,
+        fund_id: InvestmentFundId,
+        shares: Share,
+        price_per_share: Balance,
+    ) -> Result<OrderId>;
 
-    #[ink(storage)]
-    #[derive(Default)]
-    pub struct PredictionMarket {
-        pub events: Mapping<EventId, Event>,
-        pub event_markets: Mapping<EventId, EventMarket>,
-        pub outcomes: Mapping<OutComeId, OutCome>,
-        pub market_outcomes: Mapping<OutComeId, MarketOutCome>,
-        pub investment_funds: Mapping<InvestmentFundId, InvestmentFund>,
-        pub fund_trades: Mapping<TradeId, FundTrade>,
+    #[ink(message)]
+    fn place_ask(
+        &mut self,
+        fund_id: InvestmentFundId,
+        shares: Share,
+        price_per_share: Balance,
+    ) -> Result<OrderId>;
 
-        pub event_to_outcomes: Mapping<EventId, Vec<OutComeId>>,
-        pub outcome_fund_to_supplies: Mapping<(OutComeId, InvestmentFundId), Supply>,
-        pub outcome_to_funds: Mapping<OutComeId, Vec<InvestmentFundId>>,
-        pub fund_to_outcomes: Mapping<InvestmentFundId, Vec<OutComeId>>,
-        pub fund_owner_to_shares: Mapping<(InvestmentFundId, AccountId), Share>,
-        pub fund_to_trades: Mapping<InvestmentFundId, Vec<TradeId>>,
-        pub proponent_to_trades: Mapping<AccountId, Vec<TradeId>>,
+    #[ink(message)]
+    fn cancel_order(&mut self, order_id: OrderId) -> Result<()>;
 
-        pub next_fund_id: InvestmentFundId,
-        pub next_event_id: EventId,
-        pub next_outcome_id: OutComeId,
-        pub next_trade_id: TradeId,
-    }
+    #[ink(message)]
+    fn get_order_book(&self, fund_id: InvestmentFundId, depth: u32) -> Result<OrderBook>;
 
-    impl PredictionMarket {
-        #[ink(constructor)]
-        pub fn default() -> Self {
-            Default::default()
-        }
-    }
+    #[ink(message)]
+    fn get_orders(&self, owner: AccountId) -> Result<Vec<Order>>;
+}
 
-    impl super::EventCore for PredictionMarket {
-        #[ink(message, payable)]
-        fn create_event(
-            &mut self,
-            question: String,
-            resolve_date: Timestamp,
-            bets: Vec<OutComePayload>,
-            metadata: EventMetadata,
-        ) -> Result<EventId> {
-            let creation_deposit = self.env().transferred_value();
-            if creation_deposit < MIN_EVENT_DEPOSIT {
-                return Err(Error::DepositTooLow);
-            }
+#[ink::trait_definition]
+pub trait GovernanceCore {
+    #[ink(message)]
+    fn propose_governance(
+        &mut self,
+        fund_id: InvestmentFundId,
+        action: GovernanceAction,
+    ) -> Result<GovernanceId>;
 
-            let new_event = self.new_event(self.env().caller(), question, bets, metadata)?;
+    #[ink(message)]
+    fn vote_governance(&mut self, proposal_id: GovernanceId, support: bool) -> Result<()>;
 
-            let new_market =
-                self.new_market(new_event.0.event_id, creation_deposit, resolve_date)?;
+    #[ink(message)]
+    fn execute_governance(&mut self, proposal_id: GovernanceId) -> Result<GovernanceStatus>;
 
-            self.event_to_outcomes
-                .insert(new_event.0.event_id, &new_event.1);
-            self.events.insert(new_event.0.event_id, &new_event.0);
-            self.event_markets.insert(new_event.0.event_id, &new_market);
+    #[ink(message)]
+    fn get_governance_proposals(
+        &self,
+        fund_id: InvestmentFundId,
+        status: Option<GovernanceStatus>,
+    ) -> Result<Vec<GovernanceProposal>>;
+}
 
-            Ok(new_event.0.event_id)
-        }
+// Outcome positions held by accounts, keyed by `OutComeId`. This is a partial implementation:
+// only account-held claims are tokens yet. Supplies held by investment funds aren't balances of
+// anyone, so they can't be transferred or seen by wallets and only move through fund trading.
+// `total_supply` counts the claims alone, so balances always add up to it.
+#[ink::trait_definition]
+pub trait PSP37 {
+    #[ink(message)]
+    fn balance_of(&self, owner: AccountId, id: Option<OutComeId>) -> Balance;
 
-        #[ink(message)]
-        fn resolve_event(&mut self, event_id: EventId, winner: OutComeId) -> Result<()> {
-            let caller = self.env().caller();
-            let event = self.get_event_by_id(event_id)?;
-            let winning_outcome = self.get_outcome_by_id(winner)?;
+    #[ink(message)]
+    fn total_supply(&self, id: Option<OutComeId>) -> Balance;
 
-            if caller != event.0.owner {
-                return Err(Error::NotOwner);
-            }
-            if self.env().block_timestamp() < event.1.resolve_date || event.1.is_resolved {
-                return Err(Error::ResolveDateNotMatch);
-            }
-            if winning_outcome.0.event_id != event_id {
-                return Err(Error::WrongEventOutCome);
-            }
+    #[ink(message)]
+    fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<OutComeId>) -> Balance;
 
-            let mut updated_market = event.1;
-            let used_supply = winning_outcome.0.total_supply - winning_outcome.1.available_supply;
-            if used_supply == 0 {
-                return Err(Error::NoBodyBetted);
-            }
-            let prize_per_supply = updated_market.pool / (used_supply as Balance);
+    #[ink(message)]
+    fn approve(
+        &mut self,
+        operator: AccountId,
+        id: Option<OutComeId>,
+        value: Balance,
+    ) -> core::result::Result<(), PSP37Error>;
 
-            let winning_funds = self.get_outcome_funds(winner)?;
-            let mut updated_funds = Vec::new();
+    #[ink(message)]
+    fn transfer(
+        &mut self,
+        to: AccountId,
+        id: OutComeId,
+        value: Balance,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP37Error>;
 
-            for mut fund in winning_funds {
-                let supply_holding = fund.1;
-                if supply_holding == 0 {
-                    return Err(Error::SomethingWrong);
-                }
+    #[ink(message)]
+    fn transfer_from(
+        &mut self,
+        from: AccountId,
+        to: AccountId,
+        id: OutComeId,
+        value: Balance,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP37Error>;
+}
 
-                let prize = supply_holding as Balance * prize_per_supply;
+#[ink::trait_definition]
+pub trait PSP37Batch {
+    #[ink(message)]
+    fn batch_transfer(
+        &mut self,
+        to: AccountId,
+        ids_amounts: Vec<(OutComeId, Balance)>,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP37Error>;
 
-                updated_market.pool -= prize;
-                fund.0.total_fund += prize;
+    #[ink(message)]
+    fn batch_transfer_from(
+        &mut self,
+        from: AccountId,
+        to: AccountId,
+        ids_amounts: Vec<(OutComeId, Balance)>,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP37Error>;
+}
 
-                updated_funds.push(fund.0);
-            }
+// PSP22 over every fund's shares at once, the fund being the asset id. Shares escrowed in
+// proposals, asks and auctions belong to no balance and aren't part of `total_supply` either.
+#[ink::trait_definition]
+pub trait PSP22MultiAsset {
+    #[ink(message)]
+    fn total_supply(&self, fund_id: InvestmentFundId) -> Balance;
 
-            updated_market.winning_outcome = Some(winner);
-            updated_market.is_resolved = true;
+    #[ink(message)]
+    fn balance_of(&self, fund_id: InvestmentFundId, owner: AccountId) -> Balance;
 
-            self.event_markets.insert(event_id, &updated_market);
-            for fund in updated_funds {
-                self.investment_funds.insert(fund.investment_fund_id, &fund);
-            }
+    #[ink(message)]
+    fn allowance(&self, fund_id: InvestmentFundId, owner: AccountId, spender: AccountId)
+        -> Balance;
 
-            Ok(())
-        }
+    #[ink(message)]
+    fn transfer(
+        &mut self,
+        fund_id: InvestmentFundId,
+        to: AccountId,
+        value: Balance,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP22Error>;
 
-        #[ink(message)]
-        fn get_events(&self) -> Result<Vec<(Event, EventMarket, Supply)>> {
-            let mut events = Vec::new();
-            for i in 0..self.next_event_id {
-                events.push(self.get_event_by_id(i)?)
-            }
-            Ok(events)
-        }
+    #[ink(message)]
+    fn transfer_from(
+        &mut self,
+        fund_id: InvestmentFundId,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP22Error>;
 
-        #[ink(message)]
-        fn get_event_detail(
-            &self,
-            event_id: EventId,
-        ) -> Result<(
-            Event,
-            EventMarket,
-            Supply,
-            Vec<(OutCome, MarketOutCome, Vec<(InvestmentFund, Supply)>)>,
-        )> {
-            let event = self.get_event_by_id(event_id)?;
-            let event_outcome_ids = self.event_to_outcomes.get(event_id).unwrap_or_default();
-            let mut rs = Vec::new();
+    #[ink(message)]
+    fn approve(
+        &mut self,
+        fund_id: InvestmentFundId,
+        spender: AccountId,
+        value: Balance,
+    ) -> core::result::Result<(), PSP22Error>;
 
-            for i in event_outcome_ids {
-                let outcome = self.get_outcome_by_id(i)?;
-                let outcome_funds = self.get_outcome_funds(i)?;
+    #[ink(message)]
+    fn increase_allowance(
+        &mut self,
+        fund_id: InvestmentFundId,
+        spender: AccountId,
+        delta_value: Balance,
+    ) -> core::result::Result<(), PSP22Error>;
 
-                rs.push((outcome.0, outcome.1, outcome_funds));
-            }
+    #[ink(message)]
+    fn decrease_allowance(
+        &mut self,
+        fund_id: InvestmentFundId,
+        spender: AccountId,
+        delta_value: Balance,
+    ) -> core::result::Result<(), PSP22Error>;
 
-            Ok((event.0, event.1, event.2, rs))
-        }
+    #[ink(message)]
+    fn token_name(&self, fund_id: InvestmentFundId) -> Option<String>;
+
+    #[ink(message)]
+    fn token_decimals(&self, fund_id: InvestmentFundId) -> u8;
+}
+
+// The subset of PSP22 used to move collateral tokens.
+#[ink::trait_definition]
+pub trait PSP22 {
+    #[ink(message)]
+    fn transfer(
+        &mut self,
+        to: AccountId,
+        value: Balance,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP22Error>;
+
+    #[ink(message)]
+    fn transfer_from(
+        &mut self,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+        data: Vec<u8>,
+    ) -> core::result::Result<(), PSP22Error>;
+}
+
+// Markets and funds denominated in a PSP22 token take payment from a credit the caller deposits
+// beforehand. A payable message debits only what it uses: the exact cost when it prices the
+// trade, or the `deposit` argument when the caller picks the amount. The rest stays as credit.
+#[ink::trait_definition]
+pub trait CollateralCore {
+    #[ink(message)]
+    fn deposit_collateral(&mut self, token: AccountId, amount: Balance) -> Result<Balance>;
+
+    #[ink(message)]
+    fn withdraw_collateral(&mut self, token: AccountId, amount: Balance) -> Result<Balance>;
+
+    #[ink(message)]
+    fn set_collateral_allowed(&mut self, token: AccountId, allowed: bool) -> Result<()>;
+
+    #[ink(message)]
+    fn is_collateral_allowed(&self, token: AccountId) -> bool;
+
+    #[ink(message)]
+    fn get_collateral_credit(&self, owner: AccountId, token: AccountId) -> Balance;
+}
+
+#[ink::trait_definition]
+pub trait Oracle {
+    #[ink(message)]
+    fn get_answer(&self, query: Vec<u8>) -> Option<OracleAnswer>;
+}
+
+#[ink::trait_definition]
+pub trait ResolutionCore {
+    #[ink(message, payable)]
+    fn propose_resolution(&mut self, event_id: EventId, outcome_id: OutComeId) -> Result<()>;
+
+    #[ink(message, payable)]
+    fn dispute_resolution(&mut self, event_id: EventId) -> Result<()>;
+
+    #[ink(message)]
+    fn settle_dispute(&mut self, event_id: EventId, winner: Option<OutComeId>) -> Result<()>;
+
+    #[ink(message)]
+    fn finalize_resolution(&mut self, event_id: EventId) -> Result<()>;
+
+    #[ink(message)]
+    fn resolve_from_oracle(&mut self, event_id: EventId) -> Result<OutComeId>;
+
+    #[ink(message)]
+    fn resolve_scalar_from_oracle(&mut self, event_id: EventId) -> Result<OracleAnswer>;
+
+    #[ink(message)]
+    fn vote_resolution(&mut self, event_id: EventId, outcome_id: OutComeId) -> Result<()>;
+
+    #[ink(message)]
+    fn set_resolution_config(
+        &mut self,
+        arbitrator: AccountId,
+        resolution_bond: Balance,
+        dispute_window: Timestamp,
+    ) -> Result<()>;
+
+    #[ink(message)]
+    fn get_resolution(&self, event_id: EventId) -> Result<ResolutionProposal>;
+
+    #[ink(message)]
+    fn get_oracle_binding(&self, event_id: EventId) -> Result<Option<OracleBinding>>;
+
+    #[ink(message)]
+    fn get_committee(&self, event_id: EventId) -> Result<Option<Committee>>;
+
+    #[ink(message)]
+    fn get_resolution_votes(&self, event_id: EventId) -> Result<Vec<(OutComeId, u32)>>;
+
+    #[ink(message)]
+    fn get_resolution_config(&self) -> Result<(Option<AccountId>, Balance, Timestamp)>;
+}
+
+impl ink::env::Environment for MyEnvironment {
+    const MAX_EVENT_TOPICS: usize = 3;
+    type AccountId = AccountId;
+    type Balance = Balance;
+    type Hash = Hash;
+    type Timestamp = Timestamp;
+    type BlockNumber = BlockNumber;
+    type ChainExtension = ink::env::NoChainExtension;
+}
+
+// `create_event` takes far more input than any other message, which clippy flags on the dispatch
+// enum ink! generates for the messages
+#[allow(clippy::large_enum_variant)]
+#[ink::contract(env = MyEnvironment)]
+mod prediction_market {
+    use crate::*;
+    use ink::codegen::TraitCallBuilder;
+    use ink::contract_ref;
+
+    #[ink(event)]
+    pub struct EventCreated {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        owner: AccountId,
+        outcomes: Vec<OutComeId>,
+        resolve_date: Timestamp,
+        pool: Balance,
+    }
+
+    #[ink(event)]
+    pub struct MarketResolved {
+        #[ink(topic)]
+        event_id: EventId,
+        winning_outcome: OutComeId,
+        prize_per_supply: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ScalarResolved {
+        #[ink(topic)]
+        event_id: EventId,
+        value: OracleAnswer,
+        long_prize_per_supply: Balance,
+        short_prize_per_supply: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ResolutionProposed {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        proposer: AccountId,
+        outcome_id: OutComeId,
+        bond: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ResolutionDisputed {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        disputer: AccountId,
+        bond: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DisputeSettled {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        bond_winner: AccountId,
+        winner: Option<OutComeId>,
+        slashed_bond: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ResolutionVoted {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        member: AccountId,
+        outcome_id: OutComeId,
+    }
+
+    #[ink(event)]
+    pub struct MarketCancelled {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        cancelled_by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct CompleteSetMinted {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Supply,
+        cost: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CompleteSetRedeemed {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Supply,
+        proceeds: Balance,
+    }
+
+    #[ink(event)]
+    pub struct StakeRefunded {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DepositReclaimed {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct FundCreated {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        trader: AccountId,
+        total_share: Share,
+        total_fund: Balance,
+    }
+
+    #[ink(event)]
+    pub struct SharesTransferred {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        from: AccountId,
+        to: AccountId,
+        amount: Share,
+    }
+
+    #[ink(event)]
+    pub struct SharesApproved {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        owner: AccountId,
+        spender: AccountId,
+        value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BetPlaced {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        outcome_id: OutComeId,
+        supplies: Supply,
+        deposit: Balance,
+    }
+
+    #[ink(event)]
+    pub struct PositionSold {
+        #[ink(topic)]
+        event_id: EventId,
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        outcome_id: OutComeId,
+        supplies: Supply,
+        proceeds: Balance,
+    }
+
+    #[ink(event)]
+    pub struct SharesRedeemed {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        owner: AccountId,
+        shares: Share,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct SharesSubscribed {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        owner: AccountId,
+        shares: Share,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct FeesUpdated {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        fees: FundFees,
+    }
+
+    #[ink(event)]
+    pub struct FeesCollected {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        trader: AccountId,
+        amount: Balance,
+        shares: Share,
+    }
+
+    #[ink(event)]
+    pub struct DistributionPolicyUpdated {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        distribution: DistributionPolicy,
+    }
+
+    #[ink(event)]
+    pub struct DividendsDistributed {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        event_id: EventId,
+        amount: Balance,
+        dividend_per_share: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DividendsClaimed {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct SubscriptionUpdated {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        subscription: SubscriptionConfig,
+    }
+
+    #[ink(event)]
+    pub struct RedemptionClaimed {
+        #[ink(topic)]
+        outcome_id: OutComeId,
+        #[ink(topic)]
+        owner: AccountId,
+        supply: Supply,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ProposalCreated {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        proponent: AccountId,
+        trade_id: TradeId,
+        share: Share,
+        price_per_share: Balance,
+        min_fill: Share,
+        close_time: Timestamp,
+        proposed_person: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct ProposalAccepted {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        buyer: AccountId,
+        trade_id: TradeId,
+        share: Share,
+        price: Balance,
+        remaining_share: Share,
+    }
+
+    #[ink(event)]
+    pub struct ProposalCancelled {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        proponent: AccountId,
+        trade_id: TradeId,
+        share: Share,
+    }
+
+    #[ink(event)]
+    pub struct ProposalUpdated {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        proponent: AccountId,
+        trade_id: TradeId,
+        price_per_share: Balance,
+        close_time: Timestamp,
+        proposed_person: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct AuctionBid {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        bidder: AccountId,
+        trade_id: TradeId,
+        price_per_share: Balance,
+        close_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct GovernanceProposed {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        proposer: AccountId,
+        proposal_id: GovernanceId,
+        action: GovernanceAction,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct GovernanceVoted {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        voter: AccountId,
+        proposal_id: GovernanceId,
+        support: bool,
+        weight: Share,
+    }
+
+    #[ink(event)]
+    pub struct GovernanceExecuted {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        proposal_id: GovernanceId,
+        status: GovernanceStatus,
+    }
+
+    #[ink(event)]
+    pub struct OutcomeTransferred {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        id: OutComeId,
+        value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OutcomeApproval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        operator: AccountId,
+        id: Option<OutComeId>,
+        value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CollateralDeposited {
+        #[ink(topic)]
+        token: AccountId,
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CollateralWithdrawn {
+        #[ink(topic)]
+        token: AccountId,
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TraderChanged {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        new_trader: AccountId,
+        previous_trader: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OrderPlaced {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        owner: AccountId,
+        order_id: OrderId,
+        side: OrderSide,
+        share: Share,
+        price_per_share: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OrderMatched {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        taker: AccountId,
+        maker_order_id: OrderId,
+        taker_order_id: OrderId,
+        share: Share,
+        price_per_share: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OrderCancelled {
+        #[ink(topic)]
+        fund_id: InvestmentFundId,
+        #[ink(topic)]
+        owner: AccountId,
+        order_id: OrderId,
+        remaining_share: Share,
+    }
+
+    #[ink(storage)]
+    #[derive(Default)]
+    pub struct PredictionMarket {
+        pub events: Mapping<EventId, Event>,
+        pub event_markets: Mapping<EventId, EventMarket>,
+        pub outcomes: Mapping<OutComeId, OutCome>,
+        pub market_outcomes: Mapping<OutComeId, MarketOutCome>,
+        pub investment_funds: Mapping<InvestmentFundId, InvestmentFund>,
+        pub fund_trades: Mapping<TradeId, FundTrade>,
+        pub trade_fills: Mapping<(TradeId, u32), TradeFill>,
+
+        pub event_to_outcomes: Mapping<EventId, Vec<OutComeId>>,
+        // Replaces `outcome_fund_to_supplies: Mapping<_, Supply>`. Both the storage key, which
+        // comes from the field name, and the value type changed: there is no `set_code_hash`
+        // to migrate in place, so existing deployments are redeployed, and any upgrade path
+        // added later has to rewrite each old entry as `Position { supply, cost_basis: supply *
+        // deposit_per_supply }` under the new key.
+        pub outcome_fund_to_positions: Mapping<(OutComeId, InvestmentFundId), Position>,
+        pub outcome_to_funds: Mapping<OutComeId, Vec<InvestmentFundId>>,
+        pub fund_to_outcomes: Mapping<InvestmentFundId, Vec<OutComeId>>,
+        pub fund_owner_to_shares: Mapping<(InvestmentFundId, AccountId), Share>,
+        // Shares in holders' balances, i.e. the total minus whatever sits in escrow.
+        pub fund_to_circulating_share: Mapping<InvestmentFundId, Share>,
+        pub share_allowances: Mapping<ShareAllowanceKey, Balance>,
+        pub allowed_collaterals: Mapping<AccountId, bool>,
+        pub owner_token_to_credit: Mapping<(AccountId, AccountId), Balance>,
+        // Balance histories, one checkpoint per cell under its index, so they grow without
+        // ever loading the whole history.
+        pub fund_owner_to_checkpoints: Mapping<CheckpointKey, Checkpoint>,
+        pub fund_owner_to_checkpoint_count: Mapping<(InvestmentFundId, AccountId), u32>,
+        pub fund_to_total_checkpoints: Mapping<(InvestmentFundId, u32), Checkpoint>,
+        pub fund_to_total_checkpoint_count: Mapping<InvestmentFundId, u32>,
+        pub fund_owner_to_dividends: Mapping<(InvestmentFundId, AccountId), DividendAccount>,
+        pub outcome_owner_to_claims: Mapping<(OutComeId, AccountId), Position>,
+        pub owner_to_claim_outcomes: Mapping<AccountId, Vec<OutComeId>>,
+        pub outcome_to_claimed: Mapping<OutComeId, Position>,
+        pub outcome_allowances: Mapping<OutcomeAllowanceKey, Balance>,
+        pub fund_to_trades: Mapping<InvestmentFundId, Vec<TradeId>>,
+        pub event_to_resolution: Mapping<EventId, ResolutionProposal>,
+        pub event_to_oracle: Mapping<EventId, OracleBinding>,
+        pub event_to_committee: Mapping<EventId, Committee>,
+        pub event_member_to_vote: Mapping<(EventId, AccountId), OutComeId>,
+        pub proponent_to_trades: Mapping<AccountId, Vec<TradeId>>,
+        pub orders: Mapping<OrderId, Order>,
+        // Each side of a fund's book is a list linked through `next_in_book`, best price first.
+        pub book_heads: Mapping<(InvestmentFundId, OrderSide), OrderId>,
+        pub book_sizes: Mapping<(InvestmentFundId, OrderSide), u32>,
+        pub next_in_book: Mapping<OrderId, OrderId>,
+        pub owner_to_orders: Mapping<AccountId, Vec<OrderId>>,
+        pub governance_proposals: Mapping<GovernanceId, GovernanceProposal>,
+        pub fund_to_governance: Mapping<InvestmentFundId, Vec<GovernanceId>>,
+        pub governance_voter_to_vote: Mapping<(GovernanceId, AccountId), bool>,
+
+        pub next_fund_id: InvestmentFundId,
+        pub next_event_id: EventId,
+        pub next_outcome_id: OutComeId,
+        pub next_trade_id: TradeId,
+        pub next_order_id: OrderId,
+        pub next_governance_id: GovernanceId,
+
+        pub admin: Option<AccountId>,
+        pub arbitrator: Option<AccountId>,
+        pub resolution_bond: Balance,
+        pub dispute_window: Timestamp,
+        pub max_management_fee: u32,
+        pub max_performance_fee: u32,
+    }
+
+    impl PredictionMarket {
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self {
+                admin: Some(Self::env().caller()),
+                arbitrator: Some(Self::env().caller()),
+                resolution_bond: MIN_RESOLUTION_BOND,
+                dispute_window: DEFAULT_DISPUTE_WINDOW,
+                max_management_fee: MAX_MANAGEMENT_FEE,
+                max_performance_fee: MAX_PERFORMANCE_FEE,
+                ..Default::default()
+            }
+        }
+    }
+
+    impl super::EventCore for PredictionMarket {
+        #[ink(message, payable)]
+        fn create_event(
+            &mut self,
+            question: String,
+            resolve_date: Timestamp,
+            bets: Vec<OutComePayload>,
+            metadata: EventMetadata,
+            terms: MarketTerms,
+            resolver: Option<ResolverPayload>,
+        ) -> Result<EventId> {
+            self.validate_collateral(terms.collateral)?;
+            let creation_deposit = self.receive_collateral(terms.collateral, terms.deposit)?;
+            if creation_deposit < MIN_EVENT_DEPOSIT {
+                return Err(Error::DepositTooLow);
+            }
+
+            let new_event = self.new_event(self.env().caller(), question, bets, metadata)?;
+
+            let mut new_market = self.new_market(
+                new_event.0.event_id,
+                creation_deposit,
+                resolve_date,
+                terms,
+                new_event.1.len(),
+            )?;
+
+            match resolver {
+                Some(ResolverPayload::Oracle(payload)) => {
+                    let binding = self.new_oracle_binding(payload, &new_event.1)?;
+                    self.event_to_oracle.insert(new_event.0.event_id, &binding);
+                }
+                Some(ResolverPayload::Scalar(payload)) => {
+                    let binding = self.new_oracle_binding(payload, &new_event.1)?;
+                    new_market.scalar = Some(self.new_scalar_market(&binding, &new_event.1)?);
+                    self.event_to_oracle.insert(new_event.0.event_id, &binding);
+                }
+                Some(ResolverPayload::Committee(committee)) => {
+                    self.validate_committee(&committee)?;
+                    self.event_to_committee
+                        .insert(new_event.0.event_id, &committee);
+                }
+                None => {}
+            }
+
+            self.event_to_outcomes
+                .insert(new_event.0.event_id, &new_event.1);
+            self.events.insert(new_event.0.event_id, &new_event.0);
+            self.event_markets.insert(new_event.0.event_id, &new_market);
+
+            self.env().emit_event(EventCreated {
+                event_id: new_event.0.event_id,
+                owner: new_event.0.owner,
+                outcomes: new_event.1,
+                resolve_date,
+                pool: creation_deposit,
+            });
+
+            Ok(new_event.0.event_id)
+        }
+
+        #[ink(message)]
+        fn cancel_event(&mut self, event_id: EventId) -> Result<()> {
+            let caller = self.env().caller();
+            let (event, mut market, _) = self.get_event_by_id(event_id)?;
+
+            // anyone can cancel once a committee failed to agree in time
+            let committee_timed_out = self.event_to_committee.contains(event_id)
+                && self.env().block_timestamp()
+                    >= market.resolve_date.saturating_add(COMMITTEE_TIMEOUT);
+
+            if self.admin != Some(caller) && !committee_timed_out {
+                if caller != event.owner {
+                    return Err(Error::NotOwner);
+                }
+                if self.env().block_timestamp() >= market.resolve_date {
+                    return Err(Error::ResolveDateNotMatch);
+                }
+            }
+            if market.status != MarketStatus::Open {
+                return Err(Error::ResolveDateNotMatch);
+            }
+
+            market.status = MarketStatus::Invalid;
+            self.event_markets.insert(event_id, &market);
+
+            self.env().emit_event(MarketCancelled {
+                event_id,
+                cancelled_by: caller,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn claim_refund(
+            &mut self,
+            event_id: EventId,
+            fund_id: InvestmentFundId,
+        ) -> Result<Balance> {
+            let mut market = self.get_event_by_id(event_id)?.1;
+            let mut fund = self.get_fund_by_id(fund_id)?;
+
+            if market.status != MarketStatus::Invalid {
+                return Err(Error::MarketNotCancelled);
+            }
+
+            let mut refund = 0;
+            for (outcome, mut market_outcome) in self.get_event_outcomes(event_id)? {
+                let position = self.get_position(outcome.outcome_id, fund_id);
+                if position.supply == 0 {
+                    continue;
+                }
+
+                let cost = self.decrease_position(outcome.outcome_id, fund_id, position.supply)?;
+                market_outcome.available_supply += position.supply;
+                self.market_outcomes
+                    .insert(outcome.outcome_id, &market_outcome);
+                refund += cost;
+            }
+            if refund == 0 {
+                return Err(Error::NothingToClaim);
+            }
+            if refund > market.pool {
+                return Err(Error::NotEnoughBalance);
+            }
+
+            market.pool -= refund;
+            fund.total_fund += refund;
+
+            self.event_markets.insert(event_id, &market);
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(StakeRefunded {
+                event_id,
+                fund_id,
+                amount: refund,
+            });
+
+            Ok(refund)
+        }
+
+        #[ink(message)]
+        fn reclaim_deposit(&mut self, event_id: EventId) -> Result<Balance> {
+            let caller = self.env().caller();
+            let (event, mut market, _) = self.get_event_by_id(event_id)?;
+
+            if caller != event.owner {
+                return Err(Error::NotOwner);
+            }
+            if market.status != MarketStatus::Invalid {
+                return Err(Error::MarketNotCancelled);
+            }
+
+            // stakes still waiting for a refund keep priority over the deposit
+            let outstanding = self.get_outstanding_cost(event_id)?;
+            let amount = market
+                .pool
+                .saturating_sub(outstanding)
+                .min(market.creation_deposit);
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+
+            market.pool -= amount;
+            market.creation_deposit = 0;
+            self.event_markets.insert(event_id, &market);
+
+            self.pay_collateral(market.collateral, caller, amount)?;
+
+            self.env().emit_event(DepositReclaimed {
+                event_id,
+                owner: caller,
+                amount,
+            });
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        fn set_admin(&mut self, admin: AccountId) -> Result<()> {
+            if self.admin != Some(self.env().caller()) {
+                return Err(Error::NotOwner);
+            }
+
+            self.admin = Some(admin);
+
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        fn mint_complete_set(&mut self, event_id: EventId, amount: Supply) -> Result<Balance> {
+            let caller = self.env().caller();
+            let mut market = self.get_event_by_id(event_id)?.1;
+
+            // like betting, minting closes once the result may be known
+            if market.status != MarketStatus::Open
+                || self.env().block_timestamp() >= market.resolve_date
+            {
+                return Err(Error::MarketNotOpen);
+            }
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            let outcomes = self.get_event_outcomes(event_id)?;
+            let cost = self
+                .get_set_price(&market, &outcomes)?
+                .checked_mul(amount as Balance)
+                .ok_or(Error::MathOverflow)?;
+            let deposit = self.receive_collateral(market.collateral, cost)?;
+            if deposit < cost {
+                return Err(Error::DepositTooLow);
+            }
+
+            // the cost basis is spread over the set so an invalid market refunds exactly the cost
+            let count = outcomes.len() as Balance;
+            for (index, (outcome, mut market_outcome)) in outcomes.into_iter().enumerate() {
+                if market_outcome.available_supply < amount {
+                    return Err(Error::OutOfSupply);
+                }
+                market_outcome.available_supply -= amount;
+                self.market_outcomes
+                    .insert(outcome.outcome_id, &market_outcome);
+
+                let cost_basis = cost / count + if index == 0 { cost % count } else { 0 };
+                self.increase_claim(outcome.outcome_id, caller, amount, cost_basis);
+                self.env().emit_event(OutcomeTransferred {
+                    from: None,
+                    to: Some(caller),
+                    id: outcome.outcome_id,
+                    value: amount as Balance,
+                });
+            }
+
+            market.pool += cost;
+            self.event_markets.insert(event_id, &market);
+            self.refund_collateral(market.collateral, caller, deposit - cost)?;
+
+            self.env().emit_event(CompleteSetMinted {
+                event_id,
+                owner: caller,
+                amount,
+                cost,
+            });
+
+            Ok(cost)
+        }
+
+        #[ink(message)]
+        fn redeem_complete_set(&mut self, event_id: EventId, amount: Supply) -> Result<Balance> {
+            let caller = self.env().caller();
+            let mut market = self.get_event_by_id(event_id)?.1;
+
+            // redeeming at par after the resolve date would come out of the winners' share
+            if market.status != MarketStatus::Open
+                || self.env().block_timestamp() >= market.resolve_date
+            {
+                return Err(Error::MarketNotOpen);
+            }
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            let outcomes = self.get_event_outcomes(event_id)?;
+            let proceeds = self
+                .get_set_price(&market, &outcomes)?
+                .checked_mul(amount as Balance)
+                .ok_or(Error::MathOverflow)?;
+            if proceeds > market.pool {
+                return Err(Error::NotEnoughBalance);
+            }
+
+            for (outcome, mut market_outcome) in outcomes.into_iter() {
+                self.decrease_claim(outcome.outcome_id, caller, amount)?;
+                market_outcome.available_supply += amount;
+                self.market_outcomes
+                    .insert(outcome.outcome_id, &market_outcome);
+                self.env().emit_event(OutcomeTransferred {
+                    from: Some(caller),
+                    to: None,
+                    id: outcome.outcome_id,
+                    value: amount as Balance,
+                });
+            }
+
+            market.pool -= proceeds;
+            self.event_markets.insert(event_id, &market);
+            self.pay_collateral(market.collateral, caller, proceeds)?;
+
+            self.env().emit_event(CompleteSetRedeemed {
+                event_id,
+                owner: caller,
+                amount,
+                proceeds,
+            });
+
+            Ok(proceeds)
+        }
+
+        #[ink(message)]
+        fn quote_bet(&self, outcome_id: OutComeId, supplies: Supply) -> Result<BetQuote> {
+            let outcome = self.get_outcome_by_id(outcome_id)?;
+            let market = self.get_event_by_id(outcome.0.event_id)?.1;
+
+            if supplies == 0 {
+                return Err(Error::MoreThanOneSupply);
+            }
+            if outcome.1.available_supply < supplies {
+                return Err(Error::OutOfSupply);
+            }
+
+            let cost = self.get_trade_value(&market, &outcome.0, supplies, true)?;
+
+            let mut quantities = self.get_outcome_quantities(market.event_id)?;
+            for quantity in quantities.iter_mut() {
+                if quantity.0.outcome_id == outcome_id {
+                    quantity.1 += supplies;
+                }
+            }
+            let probabilities = self.get_implied_probabilities(&market, &quantities)?;
+
+            Ok(BetQuote {
+                outcome_id,
+                supplies,
+                cost,
+                probabilities,
+            })
+        }
+
+        #[ink(message)]
+        fn get_events(&self) -> Result<Vec<(Event, EventMarket, Supply)>> {
+            let mut events = Vec::new();
+            for i in 0..self.next_event_id {
+                events.push(self.get_event_by_id(i)?)
+            }
+            Ok(events)
+        }
+
+        #[ink(message)]
+        fn get_event_detail(
+            &self,
+            event_id: EventId,
+        ) -> Result<(
+            Event,
+            EventMarket,
+            Supply,
+            Vec<(OutCome, MarketOutCome, Vec<(InvestmentFund, Supply)>)>,
+        )> {
+            let event = self.get_event_by_id(event_id)?;
+            let mut rs = Vec::new();
+
+            for outcome in self.get_event_outcomes(event_id)? {
+                let outcome_funds = self.get_outcome_funds(outcome.0.outcome_id)?;
+
+                rs.push((outcome.0, outcome.1, outcome_funds));
+            }
+
+            Ok((event.0, event.1, event.2, rs))
+        }
+    }
+
+    impl super::FundCore for PredictionMarket {
+        #[ink(message, payable)]
+        fn create_fund(
+            &mut self,
+            total_share: Share,
+            metadata: FundMetadata,
+            collateral: Option<AccountId>,
+            deposit: Balance,
+        ) -> Result<InvestmentFundId> {
+            let trader = self.env().caller();
+            self.validate_collateral(collateral)?;
+            let total_fund = self.receive_collateral(collateral, deposit)?;
+
+            if total_share < 100 {
+                return Err(Error::AtLeast100Share);
+            }
+
+            if total_share < MIN_FUND_SHARE {
+                return Err(Error::NotDivisibleBy100);
+            }
+
+            if total_fund < MIN_FUND_DEPOSIT {
+                return Err(Error::DepositTooLow);
+            }
+
+            let mut new_fund = self.new_fund(trader, total_share, total_fund, metadata);
+            new_fund.collateral = collateral;
+            self.investment_funds
+                .insert(new_fund.investment_fund_id, &new_fund);
+            self.set_share(new_fund.investment_fund_id, trader, total_share);
+            self.checkpoint_total_share(new_fund.investment_fund_id, total_share);
+
+            self.env().emit_event(FundCreated {
+                fund_id: new_fund.investment_fund_id,
+                trader,
+                total_share,
+                total_fund,
+            });
+
+            Ok(new_fund.investment_fund_id)
+        }
+
+        #[ink(message)]
+        fn transfer_share(
+            &mut self,
+            fund_id: InvestmentFundId,
+            recipient: AccountId,
+            amount: Share,
+        ) -> Result<(AccountId, Share)> {
+            let sender = self.env().caller();
+            let share_of_recipient = self.move_shares(fund_id, sender, recipient, amount)?;
+
+            Ok((recipient, share_of_recipient))
+        }
+
+        #[ink(message, payable)]
+        fn bet(
+            &mut self,
+            outcome_id: OutComeId,
+            fund_id: InvestmentFundId,
+            supplies: Supply,
+        ) -> Result<()> {
+            let fund = self.get_fund_by_id(fund_id)?;
+            if fund.trader != self.env().caller() {
+                return Err(Error::NotOwner);
+            }
+            if fund.is_dissolved {
+                return Err(Error::FundDissolved);
+            }
+
+            let outcome = self.outcomes.get(outcome_id).unwrap();
+            let mut market_outcomes = self.market_outcomes.get(outcome_id).unwrap();
+
+            let mut market = self.get_event_by_id(outcome.event_id)?.1;
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            if fund.collateral != market.collateral {
+                return Err(Error::CollateralMismatch);
+            }
+            // betting closes at the resolve date, when the owner can no longer cancel either
+            if market.status != MarketStatus::Open
+                || self.env().block_timestamp() >= market.resolve_date
+            {
+                return Err(Error::ResolveDateNotMatch);
+            }
+            if (market_outcomes.available_supply as i64 - supplies as i64) < 0 {
+                return Err(Error::OutOfSupply);
+            }
+            if supplies == 0 {
+                return Err(Error::MoreThanOneSupply);
+            }
+            let cost = self.get_trade_value(&market, &outcome, supplies, true)?;
+            // token funds pay the quoted cost straight out of the fund
+            let deposit = match fund.collateral {
+                None => self.env().transferred_value(),
+                Some(_) => {
+                    if self.env().transferred_value() > 0 {
+                        return Err(Error::CollateralMismatch);
+                    }
+                    cost
+                }
+            };
+            if deposit < cost {
+                return Err(Error::DepositTooLow);
+            }
+            if cost > fund.total_fund {
+                return Err(Error::NotEnoughBalance);
+            }
+            market_outcomes.available_supply -= supplies;
+
+            fund.total_fund -= cost;
+            market.pool += cost;
+
+            self.increase_position(outcome_id, fund_id, supplies, cost);
+            self.market_outcomes.insert(outcome_id, &market_outcomes);
+            self.event_markets.insert(outcome.event_id, &market);
+            self.investment_funds.insert(fund_id, &fund);
+
+            // only the quote is charged, anything attached above it goes back to the trader
+            self.refund_collateral(fund.collateral, self.env().caller(), deposit - cost)?;
+
+            self.env().emit_event(BetPlaced {
+                event_id: outcome.event_id,
+                fund_id,
+                outcome_id,
+                supplies,
+                deposit: cost,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn sell(
+            &mut self,
+            outcome_id: OutComeId,
+            fund_id: InvestmentFundId,
+            supplies: Supply,
+        ) -> Result<Balance> {
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            if fund.trader != self.env().caller() {
+                return Err(Error::NotOwner);
+            }
+
+            let (outcome, mut market_outcome) = self.get_outcome_by_id(outcome_id)?;
+            let mut market = self.get_event_by_id(outcome.event_id)?.1;
+            // selling closes with betting, once the result may be known
+            if market.status != MarketStatus::Open
+                || self.env().block_timestamp() >= market.resolve_date
+            {
+                return Err(Error::ResolveDateNotMatch);
+            }
+            // a pari-mutuel pool has no price to sell at, buying back at par would be a free put
+            if let PricingMode::Fixed = market.pricing {
+                return Err(Error::SellNotSupported);
+            }
+            if supplies == 0 {
+                return Err(Error::MoreThanOneSupply);
+            }
+
+            if self.get_position(outcome_id, fund_id).supply < supplies {
+                return Err(Error::NotEnoughSupply);
+            }
+
+            let proceeds = self.get_trade_value(&market, &outcome, supplies, false)?;
+            if proceeds > market.pool {
+                return Err(Error::NotEnoughBalance);
+            }
+
+            market_outcome.available_supply += supplies;
+            market.pool -= proceeds;
+            fund.total_fund += proceeds;
+
+            self.decrease_position(outcome_id, fund_id, supplies)?;
+            self.market_outcomes.insert(outcome_id, &market_outcome);
+            self.event_markets.insert(outcome.event_id, &market);
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(PositionSold {
+                event_id: outcome.event_id,
+                fund_id,
+                outcome_id,
+                supplies,
+                proceeds,
+            });
+
+            Ok(proceeds)
+        }
+
+        #[ink(message)]
+        fn create_proposal(
+            &mut self,
+            fund_id: InvestmentFundId,
+            amount: Share,
+            price_per_share: Balance,
+            min_fill: Share,
+            duration: Option<Timestamp>,
+            proposed_person: Option<AccountId>,
+        ) -> Result<TradeId> {
+            let caller = self.env().caller();
+            let _ = self.get_fund_by_id(fund_id)?;
+
+            if amount == 0 || min_fill == 0 || min_fill > amount {
+                return Err(Error::NotEnoughShare);
+            }
+
+            let caller_share = self
+                .fund_owner_to_shares
+                .get((fund_id, caller))
+                .unwrap_or_default();
+
+            if caller_share < amount {
+                return Err(Error::NotEnoughShare);
+            }
+
+            // the offered shares stay in escrow until the trade is accepted
+            self.set_share(fund_id, caller, caller_share - amount);
+
+            let close_time = self.env().block_timestamp() + duration.unwrap_or(DEFAULT_DURATION);
+
+            let mut new_trade = self.new_trade(
+                fund_id,
+                caller,
+                amount,
+                price_per_share,
+                min_fill,
+                close_time,
+            );
+            new_trade.proposed_person = proposed_person;
+            self.insert_trade(&new_trade);
+
+            self.env().emit_event(ProposalCreated {
+                fund_id,
+                proponent: caller,
+                trade_id: new_trade.trade_id,
+                share: amount,
+                price_per_share,
+                min_fill,
+                close_time,
+                proposed_person,
+            });
+
+            Ok(new_trade.trade_id)
+        }
+
+        #[ink(message, payable)]
+        fn accept_proposal(&mut self, trade_id: TradeId, shares: Share) -> Result<()> {
+            let caller = self.env().caller();
+
+            let mut trade = self.get_trade_by_id(trade_id)?;
+            let collateral = self.get_fund_by_id(trade.investment_fund_id)?.collateral;
+
+            if trade.is_completed || trade.is_cancelled {
+                return Err(Error::TradeNotAvailable);
+            }
+            // english auctions only sell through bids
+            if let Some(AuctionMode::English { .. }) = trade.auction {
+                return Err(Error::TradeNotAvailable);
+            }
+            if self.env().block_timestamp() > trade.close_time {
+                return Err(Error::TimeExpired);
+            }
+            if trade.proposed_person.is_some() && caller != trade.proposed_person.unwrap() {
+                return Err(Error::NotOwner);
+            }
+            if shares == 0 || shares > trade.remaining_share {
+                return Err(Error::NotEnoughShare);
+            }
+            // the last fill may be smaller than the minimum
+            if shares < trade.min_fill && shares != trade.remaining_share {
+                return Err(Error::NotEnoughShare);
+            }
+            let price = (shares as Balance)
+                .checked_mul(self.get_current_price(&trade)?)
+                .ok_or(Error::MathOverflow)?;
+            let transferred_value = self.receive_collateral(collateral, price)?;
+            if transferred_value < price {
+                return Err(Error::DepositTooLow);
+            }
+
+            self.fill_trade(&mut trade, caller, shares, price)?;
+            self.refund_collateral(collateral, caller, transferred_value - price)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn cancel_proposal(&mut self, trade_id: TradeId) -> Result<()> {
+            let caller = self.env().caller();
+            let mut trade = self.get_trade_by_id(trade_id)?;
+
+            if caller != trade.proponent {
+                return Err(Error::NotOwner);
+            }
+            if trade.is_completed || trade.is_cancelled {
+                return Err(Error::TradeNotAvailable);
+            }
+            // the best bidder's funds are locked until the auction settles
+            if trade.best_bid.is_some() {
+                return Err(Error::TradeNotAvailable);
+            }
+
+            let proponent_share = self
+                .fund_owner_to_shares
+                .get((trade.investment_fund_id, caller))
+                .unwrap_or_default();
+
+            trade.is_cancelled = true;
+
+            self.fund_trades.insert(trade_id, &trade);
+            self.set_share(
+                trade.investment_fund_id,
+                caller,
+                proponent_share + trade.remaining_share,
+            );
+
+            self.env().emit_event(ProposalCancelled {
+                fund_id: trade.investment_fund_id,
+                proponent: caller,
+                trade_id,
+                share: trade.remaining_share,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn update_proposal(
+            &mut self,
+            trade_id: TradeId,
+            price_per_share: Balance,
+            close_time: Timestamp,
+            proposed_person: Option<AccountId>,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            let mut trade = self.get_trade_by_id(trade_id)?;
+
+            if caller != trade.proponent {
+                return Err(Error::NotOwner);
+            }
+            if trade.is_completed || trade.is_cancelled || trade.auction.is_some() {
+                return Err(Error::TradeNotAvailable);
+            }
+            if close_time <= self.env().block_timestamp() {
+                return Err(Error::TimeExpired);
+            }
+
+            trade.price_per_share = price_per_share;
+            trade.close_time = close_time;
+            trade.proposed_person = proposed_person;
+            self.fund_trades.insert(trade_id, &trade);
+
+            self.env().emit_event(ProposalUpdated {
+                fund_id: trade.investment_fund_id,
+                proponent: caller,
+                trade_id,
+                price_per_share,
+                close_time,
+                proposed_person,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn create_auction(
+            &mut self,
+            fund_id: InvestmentFundId,
+            amount: Share,
+            start_price: Balance,
+            duration: Option<Timestamp>,
+            auction: AuctionMode,
+        ) -> Result<TradeId> {
+            let caller = self.env().caller();
+            let fund = self.get_fund_by_id(fund_id)?;
+
+            if amount == 0 {
+                return Err(Error::NotEnoughShare);
+            }
+            match auction {
+                AuctionMode::English {
+                    min_increment: 0, ..
+                } => {
+                    return Err(Error::InvalidAuction);
+                }
+                AuctionMode::Dutch { floor_price } if floor_price > start_price => {
+                    return Err(Error::InvalidAuction);
+                }
+                _ => {}
+            }
+
+            let caller_share = self
+                .fund_owner_to_shares
+                .get((fund_id, caller))
+                .unwrap_or_default();
+
+            if caller_share < amount {
+                return Err(Error::NotEnoughShare);
+            }
+            // the block goes to a single buyer, so the trader either keeps 51% or sells it
+            if caller == fund.trader
+                && (caller_share - amount) * 100 / fund.total_share < 51
+                && amount * 100 / fund.total_share < 51
+            {
+                return Err(Error::TraderNotIdentitied);
+            }
+
+            // the block stays in escrow until the auction settles
+            self.set_share(fund_id, caller, caller_share - amount);
+
+            let close_time = self.env().block_timestamp() + duration.unwrap_or(DEFAULT_DURATION);
+
+            // auctions sell the whole block at once
+            let mut new_trade =
+                self.new_trade(fund_id, caller, amount, start_price, amount, close_time);
+            new_trade.auction = Some(auction);
+            self.insert_trade(&new_trade);
+
+            self.env().emit_event(ProposalCreated {
+                fund_id,
+                proponent: caller,
+                trade_id: new_trade.trade_id,
+                share: amount,
+                price_per_share: start_price,
+                min_fill: amount,
+                close_time,
+                proposed_person: None,
+            });
+
+            Ok(new_trade.trade_id)
+        }
+
+        #[ink(message, payable)]
+        fn bid_auction(&mut self, trade_id: TradeId, price_per_share: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            let now = self.env().block_timestamp();
+
+            let mut trade = self.get_trade_by_id(trade_id)?;
+            let collateral = self.get_fund_by_id(trade.investment_fund_id)?.collateral;
+
+            if trade.is_completed || trade.is_cancelled {
+                return Err(Error::TradeNotAvailable);
+            }
+            let (min_increment, extension) = match trade.auction {
+                Some(AuctionMode::English {
+                    min_increment,
+                    extension,
+                }) => (min_increment, extension),
+                _ => return Err(Error::TradeNotAvailable),
+            };
+            if now > trade.close_time {
+                return Err(Error::TimeExpired);
+            }
+            if caller == trade.proponent {
+                return Err(Error::NotOwner);
+            }
+
+            let min_price = match &trade.best_bid {
+                Some(best_bid) => best_bid
+                    .price_per_share
+                    .checked_add(min_increment)
+                    .ok_or(Error::MathOverflow)?,
+                None => trade.price_per_share,
+            };
+            if price_per_share < min_price {
+                return Err(Error::BidTooLow);
+            }
+            let price = (trade.remaining_share as Balance)
+                .checked_mul(price_per_share)
+                .ok_or(Error::MathOverflow)?;
+            let transferred_value = self.receive_collateral(collateral, price)?;
+            if transferred_value < price {
+                return Err(Error::DepositTooLow);
+            }
+
+            let outbid = trade.best_bid.replace(TradeBid {
+                bidder: caller,
+                price_per_share,
+                placed_at: now,
+            });
+            if trade.close_time < now + extension {
+                trade.close_time = now + extension;
+            }
+            self.fund_trades.insert(trade_id, &trade);
+
+            if let Some(outbid) = outbid {
+                self.pay_collateral(
+                    collateral,
+                    outbid.bidder,
+                    trade.remaining_share as Balance * outbid.price_per_share,
+                )?;
+            }
+            self.refund_collateral(collateral, caller, transferred_value - price)?;
+
+            self.env().emit_event(AuctionBid {
+                fund_id: trade.investment_fund_id,
+                bidder: caller,
+                trade_id,
+                price_per_share,
+                close_time: trade.close_time,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn settle_auction(&mut self, trade_id: TradeId) -> Result<()> {
+            let mut trade = self.get_trade_by_id(trade_id)?;
+
+            if trade.is_completed || trade.is_cancelled {
+                return Err(Error::TradeNotAvailable);
+            }
+            if !matches!(trade.auction, Some(AuctionMode::English { .. })) {
+                return Err(Error::TradeNotAvailable);
+            }
+            if self.env().block_timestamp() <= trade.close_time {
+                return Err(Error::ResolveDateNotMatch);
+            }
+            // without bids the proponent takes the block back through cancel_proposal
+            let (bidder, price_per_share) = match &trade.best_bid {
+                Some(best_bid) => (best_bid.bidder, best_bid.price_per_share),
+                None => return Err(Error::NoBodyBetted),
+            };
+
+            let fund_id = trade.investment_fund_id;
+            let shares = trade.remaining_share;
+            let price = shares as Balance * price_per_share;
+
+            // a sale that would leave the fund without a 51% trader can't go through, so the
+            // bid is refunded and the block goes back to the proponent
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            let proponent_share = self
+                .fund_owner_to_shares
+                .get((fund_id, trade.proponent))
+                .unwrap_or_default();
+            let bidder_share = self
+                .fund_owner_to_shares
+                .get((fund_id, bidder))
+                .unwrap_or_default();
+            if self
+                .update_trader(
+                    &mut fund,
+                    trade.proponent,
+                    proponent_share,
+                    bidder,
+                    bidder_share + shares,
+                )
+                .is_err()
+            {
+                trade.is_cancelled = true;
+                self.fund_trades.insert(trade_id, &trade);
+                self.set_share(fund_id, trade.proponent, proponent_share + shares);
+                self.pay_collateral(fund.collateral, bidder, price)?;
+
+                self.env().emit_event(ProposalCancelled {
+                    fund_id,
+                    proponent: trade.proponent,
+                    trade_id,
+                    share: shares,
+                });
+                return Ok(());
+            }
+
+            self.fill_trade(&mut trade, bidder, shares, price)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn redeem(&mut self, fund_id: InvestmentFundId, shares: Share) -> Result<Balance> {
+            let caller = self.env().caller();
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            self.accrue_fees(&mut fund, false)?;
+            let caller_share = self
+                .fund_owner_to_shares
+                .get((fund_id, caller))
+                .unwrap_or_default();
+
+            if shares == 0 || shares > caller_share {
+                return Err(Error::NotEnoughShare);
+            }
+            // the trader keeps control of a fund that still has other holders
+            if caller == fund.trader
+                && shares < fund.total_share
+                && ((caller_share - shares) * 100 / (fund.total_share - shares)) < 51
+            {
+                return Err(Error::TraderNotIdentitied);
+            }
+
+            let amount = math::mul_div(
+                fund.total_fund,
+                shares as Balance,
+                fund.total_share as Balance,
+            )
+            .ok_or(Error::MathOverflow)?;
+
+            // open positions can't be liquidated, the holder keeps a claim on their part
+            for outcome_id in self.fund_to_outcomes.get(fund_id).unwrap_or_default() {
+                let outcome = self.get_outcome_by_id(outcome_id)?;
+                let market = self.get_event_by_id(outcome.0.event_id)?.1;
+                if market.status == MarketStatus::Resolved {
+                    continue;
+                }
+
+                let position = self.get_position(outcome_id, fund_id);
+                let supply =
+                    (position.supply as u128 * shares as u128 / fund.total_share as u128) as Supply;
+                if supply == 0 {
+                    continue;
+                }
+                let cost = self.decrease_position(outcome_id, fund_id, supply)?;
+                self.increase_claim(outcome_id, caller, supply, cost);
+                self.env().emit_event(OutcomeTransferred {
+                    from: None,
+                    to: Some(caller),
+                    id: outcome_id,
+                    value: supply as Balance,
+                });
+            }
+
+            fund.total_fund -= amount;
+            fund.total_share -= shares;
+
+            self.set_share(fund_id, caller, caller_share - shares);
+            self.checkpoint_total_share(fund_id, fund.total_share);
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.pay_collateral(fund.collateral, caller, amount)?;
+
+            self.env().emit_event(SharesRedeemed {
+                fund_id,
+                owner: caller,
+                shares,
+                amount,
+            });
+
+            Ok(amount)
+        }
+
+        #[ink(message, payable)]
+        fn subscribe(&mut self, fund_id: InvestmentFundId, deposit: Balance) -> Result<Share> {
+            let caller = self.env().caller();
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            let deposit = self.receive_collateral(fund.collateral, deposit)?;
+
+            if fund.is_dissolved {
+                return Err(Error::FundDissolved);
+            }
+            if !fund.subscription.is_open {
+                return Err(Error::SubscriptionClosed);
+            }
+            if deposit < fund.subscription.min_ticket {
+                return Err(Error::DepositTooLow);
+            }
+            // fees owed so far are charged before the new money comes in
+            self.accrue_fees(&mut fund, false)?;
+
+            let net_asset_value = self.get_fund_valuation(&fund)?.net_asset_value;
+            if net_asset_value == 0 || fund.total_share == 0 {
+                return Err(Error::ZeroNetAssetValue);
+            }
+            let shares = math::mul_div(deposit, fund.total_share as Balance, net_asset_value)
+                .and_then(|shares| Share::try_from(shares).ok())
+                .ok_or(Error::MathOverflow)?;
+            if shares == 0 {
+                return Err(Error::DepositTooLow);
+            }
+            if let Some(share_cap) = fund.subscription.share_cap {
+                if fund.total_share + shares > share_cap {
+                    return Err(Error::SubscriptionCapReached);
+                }
+            }
+
+            // new money can't dilute the trader below the 51% that keeps them in control
+            let trader_share = self
+                .fund_owner_to_shares
+                .get((fund_id, fund.trader))
+                .unwrap_or_default();
+            if caller != fund.trader && trader_share * 100 / (fund.total_share + shares) < 51 {
+                return Err(Error::TraderNotIdentitied);
+            }
+
+            let caller_share = self
+                .fund_owner_to_shares
+                .get((fund_id, caller))
+                .unwrap_or_default();
+
+            fund.total_fund += deposit;
+            fund.total_share += shares;
+
+            self.set_share(fund_id, caller, caller_share + shares);
+            self.checkpoint_total_share(fund_id, fund.total_share);
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(SharesSubscribed {
+                fund_id,
+                owner: caller,
+                shares,
+                amount: deposit,
+            });
+
+            Ok(shares)
+        }
+
+        #[ink(message)]
+        fn set_subscription(
+            &mut self,
+            fund_id: InvestmentFundId,
+            subscription: SubscriptionConfig,
+        ) -> Result<()> {
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            if fund.trader != self.env().caller() {
+                return Err(Error::NotOwner);
+            }
+            if fund.is_dissolved {
+                return Err(Error::FundDissolved);
+            }
+
+            fund.subscription = subscription;
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(SubscriptionUpdated {
+                fund_id,
+                subscription,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn set_fees(&mut self, fund_id: InvestmentFundId, fees: FundFees) -> Result<()> {
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            if fund.trader != self.env().caller() {
+                return Err(Error::NotOwner);
+            }
+            self.validate_fees(&fees)?;
+
+            // what accrued under the old rates is settled first
+            self.accrue_fees(&mut fund, false)?;
+            fund.fees = fees;
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(FeesUpdated { fund_id, fees });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn collect_fees(&mut self, fund_id: InvestmentFundId) -> Result<Balance> {
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            let amount = self.accrue_fees(&mut fund, false)?;
+            self.investment_funds.insert(fund_id, &fund);
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        fn set_fee_limits(
+            &mut self,
+            max_management_fee: u32,
+            max_performance_fee: u32,
+        ) -> Result<()> {
+            if self.admin != Some(self.env().caller()) {
+                return Err(Error::NotOwner);
+            }
+            if max_management_fee > BASIS_POINTS || max_performance_fee > BASIS_POINTS {
+                return Err(Error::InvalidFee);
+            }
+
+            self.max_management_fee = max_management_fee;
+            self.max_performance_fee = max_performance_fee;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn get_fee_limits(&self) -> Result<(u32, u32)> {
+            Ok((self.max_management_fee, self.max_performance_fee))
+        }
+
+        #[ink(message)]
+        fn set_distribution_policy(
+            &mut self,
+            fund_id: InvestmentFundId,
+            distribution: DistributionPolicy,
+        ) -> Result<()> {
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            if fund.trader != self.env().caller() {
+                return Err(Error::NotOwner);
+            }
+
+            fund.distribution = distribution;
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(DistributionPolicyUpdated {
+                fund_id,
+                distribution,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn claim_dividends(&mut self, fund_id: InvestmentFundId) -> Result<Balance> {
+            let caller = self.env().caller();
+            let fund = self.get_fund_by_id(fund_id)?;
+
+            let mut account = self.settle_dividends(&fund, caller);
+            let amount = account.pending;
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+            account.pending = 0;
+            self.fund_owner_to_dividends
+                .insert((fund_id, caller), &account);
+
+            self.pay_collateral(fund.collateral, caller, amount)?;
+
+            self.env().emit_event(DividendsClaimed {
+                fund_id,
+                owner: caller,
+                amount,
+            });
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        fn get_dividends(&self, fund_id: InvestmentFundId, owner: AccountId) -> Result<Balance> {
+            let fund = self.get_fund_by_id(fund_id)?;
+            Ok(self.settle_dividends(&fund, owner).pending)
+        }
+
+        #[ink(message)]
+        fn claim_redemption(&mut self, outcome_id: OutComeId) -> Result<Balance> {
+            let caller = self.env().caller();
+            let (outcome, mut market_outcome) = self.get_outcome_by_id(outcome_id)?;
+            let mut market = self.get_event_by_id(outcome.event_id)?.1;
+            let claim = self
+                .outcome_owner_to_claims
+                .get((outcome_id, caller))
+                .ok_or(Error::NothingToClaim)?;
+
+            let amount = match market.status {
+                MarketStatus::Resolved => {
+                    claim.supply as Balance * self.get_prize_per_supply(&market, outcome_id)
+                }
+                MarketStatus::Invalid => {
+                    market_outcome.available_supply += claim.supply;
+                    self.market_outcomes.insert(outcome_id, &market_outcome);
+                    claim.cost_basis
+                }
+                _ => return Err(Error::ResolveDateNotMatch),
+            };
+            if amount > market.pool {
+                return Err(Error::NotEnoughBalance);
+            }
+
+            market.pool -= amount;
+            self.decrease_claim(outcome_id, caller, claim.supply)?;
+            self.event_markets.insert(outcome.event_id, &market);
+
+            self.pay_collateral(market.collateral, caller, amount)?;
+
+            self.env().emit_event(OutcomeTransferred {
+                from: Some(caller),
+                to: None,
+                id: outcome_id,
+                value: claim.supply as Balance,
+            });
+            self.env().emit_event(RedemptionClaimed {
+                outcome_id,
+                owner: caller,
+                supply: claim.supply,
+                amount,
+            });
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        fn get_redemption_claims(&self, owner: AccountId) -> Result<Vec<(OutCome, Position)>> {
+            let mut rs = Vec::new();
+            for outcome_id in self.owner_to_claim_outcomes.get(owner).unwrap_or_default() {
+                rs.push((
+                    self.get_outcome_by_id(outcome_id)?.0,
+                    self.outcome_owner_to_claims
+                        .get((outcome_id, owner))
+                        .unwrap_or_default(),
+                ));
+            }
+            Ok(rs)
+        }
+
+        #[ink(message)]
+        fn get_funds(
+            &self,
+            owner: Option<AccountId>,
+        ) -> Result<
+            Vec<(
+                InvestmentFund,
+                Vec<(OutCome, Supply)>,
+                Option<Share>,
+                Balance,
+            )>,
+        > {
+            let mut rs = Vec::new();
+
+            for i in 0..self.next_fund_id {
+                let fund = self.get_fund_by_id(i)?;
+                let fund_outcomes = self.get_fund_outcomes(i)?;
+                let nav_per_share = self.get_fund_valuation(&fund)?.nav_per_share;
+                match owner {
+                    Some(owner) => {
+                        let owner_share = self
+                            .fund_owner_to_shares
+                            .get((fund.investment_fund_id, owner))
+                            .unwrap_or_default();
+
+                        if owner_share != 0 {
+                            rs.push((fund, fund_outcomes, Some(owner_share), nav_per_share));
+                        }
+                    }
+                    None => rs.push((fund, fund_outcomes, None, nav_per_share)),
+                }
+            }
+
+            Ok(rs)
+        }
+
+        #[ink(message)]
+        fn get_fund_nav(&self, fund_id: InvestmentFundId) -> Result<FundValuation> {
+            let fund = self.get_fund_by_id(fund_id)?;
+            self.get_fund_valuation(&fund)
+        }
+
+        #[ink(message)]
+        fn get_owner_share(&self, fund_id: InvestmentFundId, owner: AccountId) -> Result<Share> {
+            let owner_share = self
+                .fund_owner_to_shares
+                .get((fund_id, owner))
+                .unwrap_or_default();
+
+            Ok(owner_share)
+        }
+
+        #[ink(message)]
+        fn get_share_at(
+            &self,
+            fund_id: InvestmentFundId,
+            owner: AccountId,
+            block: BlockNumber,
+        ) -> Result<Share> {
+            let _ = self.get_fund_by_id(fund_id)?;
+            Ok(self.get_checkpointed_share(fund_id, owner, block))
+        }
+
+        #[ink(message)]
+        fn get_total_share_at(
+            &self,
+            fund_id: InvestmentFundId,
+            block: BlockNumber,
+        ) -> Result<Share> {
+            let _ = self.get_fund_by_id(fund_id)?;
+            Ok(self.get_checkpointed_total_share(fund_id, block))
+        }
+
+        #[ink(message)]
+        fn get_proposals(
+            &self,
+            proponent: Option<AccountId>,
+            status: Option<TradeStatus>,
+        ) -> Result<Vec<(InvestmentFund, FundTrade)>> {
+            let trade_ids = match proponent {
+                Some(id) => self.proponent_to_trades.get(id).unwrap_or_default(),
+                None => (0..self.next_trade_id).collect(),
+            };
+
+            let mut rs = Vec::new();
+            for trade_id in trade_ids.into_iter() {
+                let trade = self.get_trade_by_id(trade_id)?;
+                if status.is_some() && status != Some(self.get_trade_status(&trade)) {
+                    continue;
+                }
+                let fund = self.get_fund_by_id(trade.investment_fund_id)?;
+
+                rs.push((fund, trade));
+            }
+
+            Ok(rs)
+        }
+
+        #[ink(message)]
+        fn get_fund_proposals(
+            &self,
+            fund_id: InvestmentFundId,
+            status: Option<TradeStatus>,
+        ) -> Result<(InvestmentFund, Vec<FundTrade>)> {
+            let fund = self.get_fund_by_id(fund_id)?;
+            let trade_ids = self.fund_to_trades.get(fund_id).unwrap_or_default();
+            let mut trades = Vec::new();
+
+            for trade_id in trade_ids.into_iter() {
+                let trade = self.get_trade_by_id(trade_id)?;
+                if status.is_some() && status != Some(self.get_trade_status(&trade)) {
+                    continue;
+                }
+                trades.push(trade);
+            }
+
+            Ok((fund, trades))
+        }
+
+        #[ink(message)]
+        fn get_trade_price(&self, trade_id: TradeId) -> Result<Balance> {
+            let trade = self.get_trade_by_id(trade_id)?;
+            self.get_current_price(&trade)
+        }
+
+        #[ink(message)]
+        fn get_trade_fills(&self, trade_id: TradeId) -> Result<Vec<TradeFill>> {
+            let trade = self.get_trade_by_id(trade_id)?;
+            Ok((0..trade.fill_count)
+                .filter_map(|index| self.trade_fills.get((trade_id, index)))
+                .collect())
+        }
+    }
+
+    impl super::OrderBookCore for PredictionMarket {
+        #[ink(message, payable)]
+        fn place_bid(
+            &mut self,
+            fund_id: InvestmentFundId,
+            shares: Share,
+            price_per_share: Balance,
+        ) -> Result<OrderId> {
+            let caller = self.env().caller();
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            let previous_trader = fund.trader;
+            let escrow = self.validate_order(shares, price_per_share)?;
+            let transferred_value = self.receive_collateral(fund.collateral, escrow)?;
+            if transferred_value < escrow {
+                return Err(Error::DepositTooLow);
+            }
+
+            let mut order =
+                self.new_order(fund_id, caller, OrderSide::Bid, shares, price_per_share);
+            self.match_order(&mut fund, &mut order)?;
+            self.save_order(&order)?;
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.refund_collateral(fund.collateral, caller, transferred_value - escrow)?;
+
+            self.env().emit_event(OrderPlaced {
+                fund_id,
+                owner: caller,
+                order_id: order.order_id,
+                side: OrderSide::Bid,
+                share: shares,
+                price_per_share,
+            });
+            if fund.trader != previous_trader {
+                self.env().emit_event(TraderChanged {
+                    fund_id,
+                    new_trader: fund.trader,
+                    previous_trader,
+                });
+            }
+
+            Ok(order.order_id)
+        }
+
+        #[ink(message)]
+        fn place_ask(
+            &mut self,
+            fund_id: InvestmentFundId,
+            shares: Share,
+            price_per_share: Balance,
+        ) -> Result<OrderId> {
+            let caller = self.env().caller();
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            let previous_trader = fund.trader;
+
+            self.validate_order(shares, price_per_share)?;
+            let caller_share = self
+                .fund_owner_to_shares
+                .get((fund_id, caller))
+                .unwrap_or_default();
+            if caller_share < shares {
+                return Err(Error::NotEnoughShare);
+            }
+            // the book fills in pieces, so the trader has to keep control whatever gets filled;
+            // handing over the fund goes through a proposal instead
+            if caller == fund.trader && (caller_share - shares) * 100 / fund.total_share < 51 {
+                return Err(Error::TraderNotIdentitied);
+            }
+
+            // the offered shares stay in escrow until they are matched or the order is cancelled
+            self.set_share(fund_id, caller, caller_share - shares);
+
+            let mut order =
+                self.new_order(fund_id, caller, OrderSide::Ask, shares, price_per_share);
+            self.match_order(&mut fund, &mut order)?;
+            self.save_order(&order)?;
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(OrderPlaced {
+                fund_id,
+                owner: caller,
+                order_id: order.order_id,
+                side: OrderSide::Ask,
+                share: shares,
+                price_per_share,
+            });
+            if fund.trader != previous_trader {
+                self.env().emit_event(TraderChanged {
+                    fund_id,
+                    new_trader: fund.trader,
+                    previous_trader,
+                });
+            }
+
+            Ok(order.order_id)
+        }
+
+        #[ink(message)]
+        fn cancel_order(&mut self, order_id: OrderId) -> Result<()> {
+            let caller = self.env().caller();
+            let mut order = self.get_order_by_id(order_id)?;
+
+            if caller != order.owner {
+                return Err(Error::NotOwner);
+            }
+            if order.is_cancelled || order.remaining_share == 0 {
+                return Err(Error::TradeNotAvailable);
+            }
+
+            self.remove_from_book(&order);
+            self.release_order(&mut order)
+        }
+
+        #[ink(message)]
+        fn get_order_book(&self, fund_id: InvestmentFundId, depth: u32) -> Result<OrderBook> {
+            let _ = self.get_fund_by_id(fund_id)?;
+
+            Ok(OrderBook {
+                investment_fund_id: fund_id,
+                bids: self.get_book_levels(fund_id, OrderSide::Bid, depth)?,
+                asks: self.get_book_levels(fund_id, OrderSide::Ask, depth)?,
+            })
+        }
+
+        #[ink(message)]
+        fn get_orders(&self, owner: AccountId) -> Result<Vec<Order>> {
+            let order_ids = self.owner_to_orders.get(owner).unwrap_or_default();
+            let mut orders = Vec::new();
+
+            for order_id in order_ids.into_iter() {
+                orders.push(self.get_order_by_id(order_id)?);
+            }
+
+            Ok(orders)
+        }
+    }
+
+    impl super::GovernanceCore for PredictionMarket {
+        #[ink(message)]
+        fn propose_governance(
+            &mut self,
+            fund_id: InvestmentFundId,
+            action: GovernanceAction,
+        ) -> Result<GovernanceId> {
+            let caller = self.env().caller();
+            let fund = self.get_fund_by_id(fund_id)?;
+
+            if fund.is_dissolved {
+                return Err(Error::FundDissolved);
+            }
+            // weights are read from the end of the previous block so nothing moved in the
+            // proposal's own block counts; the first block has no previous one to read
+            let snapshot_block = self
+                .env()
+                .block_number()
+                .checked_sub(1)
+                .ok_or(Error::NotShareholder)?;
+            if self.get_checkpointed_share(fund_id, caller, snapshot_block) == 0 {
+                return Err(Error::NotShareholder);
+            }
+            if let GovernanceAction::ChangeFee(fees) = action {
+                self.validate_fees(&fees)?;
+            }
+
+            let proposal_id = self.next_governance_id;
+            self.next_governance_id += 1;
+
+            let end_time = self.env().block_timestamp() + GOVERNANCE_VOTING_PERIOD;
+            let proposal = GovernanceProposal {
+                proposal_id,
+                investment_fund_id: fund_id,
+                proposer: caller,
+                action,
+                snapshot_block,
+                end_time,
+                votes_for: 0,
+                votes_against: 0,
+                status: GovernanceStatus::Active,
+            };
+
+            let mut proposals_of_fund = self.fund_to_governance.get(fund_id).unwrap_or_default();
+            proposals_of_fund.push(proposal_id);
+            self.fund_to_governance.insert(fund_id, &proposals_of_fund);
+            self.governance_proposals.insert(proposal_id, &proposal);
+
+            self.env().emit_event(GovernanceProposed {
+                fund_id,
+                proposer: caller,
+                proposal_id,
+                action,
+                end_time,
+            });
+
+            Ok(proposal_id)
+        }
+
+        #[ink(message)]
+        fn vote_governance(&mut self, proposal_id: GovernanceId, support: bool) -> Result<()> {
+            let caller = self.env().caller();
+            let mut proposal = self.get_governance_by_id(proposal_id)?;
+
+            if proposal.status != GovernanceStatus::Active
+                || self.env().block_timestamp() > proposal.end_time
+            {
+                return Err(Error::VotingClosed);
+            }
+            if self
+                .governance_voter_to_vote
+                .contains((proposal_id, caller))
+            {
+                return Err(Error::AlreadyVoted);
+            }
+            let weight = self.get_checkpointed_share(
+                proposal.investment_fund_id,
+                caller,
+                proposal.snapshot_block,
+            );
+            if weight == 0 {
+                return Err(Error::NotShareholder);
+            }
+
+            if support {
+                proposal.votes_for += weight;
+            } else {
+                proposal.votes_against += weight;
+            }
+            self.governance_voter_to_vote
+                .insert((proposal_id, caller), &support);
+
+            self.env().emit_event(GovernanceVoted {
+                fund_id: proposal.investment_fund_id,
+                voter: caller,
+                proposal_id,
+                support,
+                weight,
+            });
+
+            // an outright majority of all shares settles the vote without waiting for the period
+            let total_share = self
+                .get_checkpointed_total_share(proposal.investment_fund_id, proposal.snapshot_block);
+            if proposal.votes_for * 2 > total_share {
+                self.execute_action(&mut proposal)?;
+            }
+            self.governance_proposals.insert(proposal_id, &proposal);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn execute_governance(&mut self, proposal_id: GovernanceId) -> Result<GovernanceStatus> {
+            let mut proposal = self.get_governance_by_id(proposal_id)?;
+
+            if proposal.status != GovernanceStatus::Active {
+                return Err(Error::VotingClosed);
+            }
+            if self.env().block_timestamp() <= proposal.end_time {
+                return Err(Error::VotingOpen);
+            }
+
+            let fund = self.get_fund_by_id(proposal.investment_fund_id)?;
+            let total_share = self
+                .get_checkpointed_total_share(proposal.investment_fund_id, proposal.snapshot_block);
+            let turnout = proposal.votes_for + proposal.votes_against;
+            if turnout * 100 >= total_share * GOVERNANCE_QUORUM
+                && proposal.votes_for > proposal.votes_against
+                && !fund.is_dissolved
+            {
+                self.execute_action(&mut proposal)?;
+            } else {
+                proposal.status = GovernanceStatus::Rejected;
+                self.env().emit_event(GovernanceExecuted {
+                    fund_id: proposal.investment_fund_id,
+                    proposal_id,
+                    status: GovernanceStatus::Rejected,
+                });
+            }
+            self.governance_proposals.insert(proposal_id, &proposal);
+
+            Ok(proposal.status)
+        }
+
+        #[ink(message)]
+        fn get_governance_proposals(
+            &self,
+            fund_id: InvestmentFundId,
+            status: Option<GovernanceStatus>,
+        ) -> Result<Vec<GovernanceProposal>> {
+            let _ = self.get_fund_by_id(fund_id)?;
+            let proposal_ids = self.fund_to_governance.get(fund_id).unwrap_or_default();
+            let mut proposals = Vec::new();
+
+            for proposal_id in proposal_ids.into_iter() {
+                let proposal = self.get_governance_by_id(proposal_id)?;
+                if status.is_some() && status != Some(proposal.status) {
+                    continue;
+                }
+                proposals.push(proposal);
+            }
+
+            Ok(proposals)
+        }
+    }
+
+    impl super::PSP37 for PredictionMarket {
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId, id: Option<OutComeId>) -> Balance {
+            match id {
+                Some(outcome_id) => {
+                    self.outcome_owner_to_claims
+                        .get((outcome_id, owner))
+                        .unwrap_or_default()
+                        .supply as Balance
+                }
+                None => self
+                    .owner_to_claim_outcomes
+                    .get(owner)
+                    .unwrap_or_default()
+                    .len() as Balance,
+            }
+        }
+
+        #[ink(message)]
+        fn total_supply(&self, id: Option<OutComeId>) -> Balance {
+            match id {
+                Some(outcome_id) => {
+                    self.outcome_to_claimed
+                        .get(outcome_id)
+                        .unwrap_or_default()
+                        .supply as Balance
+                }
+                None => self.next_outcome_id as Balance,
+            }
+        }
+
+        #[ink(message)]
+        fn allowance(
+            &self,
+            owner: AccountId,
+            operator: AccountId,
+            id: Option<OutComeId>,
+        ) -> Balance {
+            self.get_outcome_allowance(owner, operator, id)
+        }
+
+        #[ink(message)]
+        fn approve(
+            &mut self,
+            operator: AccountId,
+            id: Option<OutComeId>,
+            value: Balance,
+        ) -> core::result::Result<(), PSP37Error> {
+            let caller = self.env().caller();
+            if operator == caller {
+                return Err(PSP37Error::NotAllowed);
+            }
+
+            // approving without an id lets the operator move every outcome
+            let value = match id {
+                None if value > 0 => Balance::MAX,
+                _ => value,
+            };
+            if value == 0 {
+                self.outcome_allowances.remove((caller, operator, id));
+            } else {
+                self.outcome_allowances
+                    .insert((caller, operator, id), &value);
+            }
+
+            self.env().emit_event(OutcomeApproval {
+                owner: caller,
+                operator,
+                id,
+                value,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn transfer(
+            &mut self,
+            to: AccountId,
+            id: OutComeId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP37Error> {
+            let caller = self.env().caller();
+            self.transfer_outcome(caller, to, id, value)
+        }
+
+        #[ink(message)]
+        fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            id: OutComeId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP37Error> {
+            self.spend_outcome_allowance(from, id, value)?;
+            self.transfer_outcome(from, to, id, value)
+        }
+    }
+
+    impl super::PSP37Batch for PredictionMarket {
+        #[ink(message)]
+        fn batch_transfer(
+            &mut self,
+            to: AccountId,
+            ids_amounts: Vec<(OutComeId, Balance)>,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP37Error> {
+            let caller = self.env().caller();
+            for (id, value) in ids_amounts.into_iter() {
+                self.transfer_outcome(caller, to, id, value)?;
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn batch_transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            ids_amounts: Vec<(OutComeId, Balance)>,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP37Error> {
+            for (id, value) in ids_amounts.into_iter() {
+                self.spend_outcome_allowance(from, id, value)?;
+                self.transfer_outcome(from, to, id, value)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl super::PSP22MultiAsset for PredictionMarket {
+        #[ink(message)]
+        fn total_supply(&self, fund_id: InvestmentFundId) -> Balance {
+            self.fund_to_circulating_share
+                .get(fund_id)
+                .unwrap_or_default() as Balance
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, fund_id: InvestmentFundId, owner: AccountId) -> Balance {
+            self.fund_owner_to_shares
+                .get((fund_id, owner))
+                .unwrap_or_default() as Balance
+        }
+
+        #[ink(message)]
+        fn allowance(
+            &self,
+            fund_id: InvestmentFundId,
+            owner: AccountId,
+            spender: AccountId,
+        ) -> Balance {
+            self.share_allowances
+                .get((fund_id, owner, spender))
+                .unwrap_or_default()
+        }
+
+        #[ink(message)]
+        fn transfer(
+            &mut self,
+            fund_id: InvestmentFundId,
+            to: AccountId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            if to == AccountId::from([0u8; 32]) {
+                return Err(PSP22Error::ZeroRecipientAddress);
+            }
+            let amount = Share::try_from(value).map_err(|_| PSP22Error::InsufficientBalance)?;
+            self.move_shares(fund_id, caller, to, amount)?;
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn transfer_from(
+            &mut self,
+            fund_id: InvestmentFundId,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            if from == AccountId::from([0u8; 32]) {
+                return Err(PSP22Error::ZeroSenderAddress);
+            }
+            if to == AccountId::from([0u8; 32]) {
+                return Err(PSP22Error::ZeroRecipientAddress);
+            }
+            let amount = Share::try_from(value).map_err(|_| PSP22Error::InsufficientBalance)?;
+
+            if caller != from {
+                let allowance = self
+                    .share_allowances
+                    .get((fund_id, from, caller))
+                    .unwrap_or_default();
+                if allowance < value {
+                    return Err(PSP22Error::InsufficientAllowance);
+                }
+                self.set_share_allowance(fund_id, from, caller, allowance - value);
+            }
+            self.move_shares(fund_id, from, to, amount)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn approve(
+            &mut self,
+            fund_id: InvestmentFundId,
+            spender: AccountId,
+            value: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            let _ = self.get_fund_by_id(fund_id)?;
+            self.set_share_allowance(fund_id, caller, spender, value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn increase_allowance(
+            &mut self,
+            fund_id: InvestmentFundId,
+            spender: AccountId,
+            delta_value: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            let _ = self.get_fund_by_id(fund_id)?;
+            let allowance = self
+                .share_allowances
+                .get((fund_id, caller, spender))
+                .unwrap_or_default();
+            self.set_share_allowance(
+                fund_id,
+                caller,
+                spender,
+                allowance.saturating_add(delta_value),
+            );
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn decrease_allowance(
+            &mut self,
+            fund_id: InvestmentFundId,
+            spender: AccountId,
+            delta_value: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            let allowance = self
+                .share_allowances
+                .get((fund_id, caller, spender))
+                .unwrap_or_default();
+            if allowance < delta_value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+            self.set_share_allowance(fund_id, caller, spender, allowance - delta_value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn token_name(&self, fund_id: InvestmentFundId) -> Option<String> {
+            self.investment_funds
+                .get(fund_id)
+                .and_then(|fund| fund.metadata.name)
+        }
+
+        #[ink(message)]
+        fn token_decimals(&self, _fund_id: InvestmentFundId) -> u8 {
+            0
+        }
+    }
+
+    impl super::CollateralCore for PredictionMarket {
+        #[ink(message)]
+        fn deposit_collateral(&mut self, token: AccountId, amount: Balance) -> Result<Balance> {
+            let caller = self.env().caller();
+            self.validate_collateral(Some(token))?;
+
+            let mut psp22: contract_ref!(PSP22, MyEnvironment) = token.into();
+            psp22
+                .call_mut()
+                .transfer_from(caller, self.env().account_id(), amount, Vec::new())
+                .try_invoke()
+                .map_err(|_| Error::TransferFailed)?
+                .map_err(|_| Error::TransferFailed)?
+                .map_err(|_| Error::TransferFailed)?;
+
+            let credit = self
+                .owner_token_to_credit
+                .get((caller, token))
+                .unwrap_or_default()
+                + amount;
+            self.owner_token_to_credit.insert((caller, token), &credit);
+
+            self.env().emit_event(CollateralDeposited {
+                token,
+                owner: caller,
+                amount,
+            });
+
+            Ok(credit)
+        }
+
+        #[ink(message)]
+        fn withdraw_collateral(&mut self, token: AccountId, amount: Balance) -> Result<Balance> {
+            let caller = self.env().caller();
+            let credit = self
+                .owner_token_to_credit
+                .get((caller, token))
+                .unwrap_or_default();
+            if credit < amount {
+                return Err(Error::NotEnoughBalance);
+            }
+
+            self.owner_token_to_credit
+                .insert((caller, token), &(credit - amount));
+            self.pay_collateral(Some(token), caller, amount)?;
+
+            self.env().emit_event(CollateralWithdrawn {
+                token,
+                owner: caller,
+                amount,
+            });
+
+            Ok(credit - amount)
+        }
+
+        #[ink(message)]
+        fn set_collateral_allowed(&mut self, token: AccountId, allowed: bool) -> Result<()> {
+            if self.admin != Some(self.env().caller()) {
+                return Err(Error::NotOwner);
+            }
+
+            if allowed {
+                self.allowed_collaterals.insert(token, &true);
+            } else {
+                self.allowed_collaterals.remove(token);
+            }
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn is_collateral_allowed(&self, token: AccountId) -> bool {
+            self.allowed_collaterals.contains(token)
+        }
+
+        #[ink(message)]
+        fn get_collateral_credit(&self, owner: AccountId, token: AccountId) -> Balance {
+            self.owner_token_to_credit
+                .get((owner, token))
+                .unwrap_or_default()
+        }
+    }
+
+    impl super::ResolutionCore for PredictionMarket {
+        #[ink(message, payable)]
+        fn propose_resolution(&mut self, event_id: EventId, outcome_id: OutComeId) -> Result<()> {
+            let caller = self.env().caller();
+            let bond = self.env().transferred_value();
+            let mut market = self.get_event_by_id(event_id)?.1;
+            let outcome = self.get_outcome_by_id(outcome_id)?;
+
+            if self.env().block_timestamp() < market.resolve_date
+                || market.status != MarketStatus::Open
+            {
+                return Err(Error::ResolveDateNotMatch);
+            }
+            if outcome.0.event_id != event_id {
+                return Err(Error::WrongEventOutCome);
+            }
+            if self.event_to_oracle.contains(event_id) || self.event_to_committee.contains(event_id)
+            {
+                return Err(Error::ResolverMismatch);
+            }
+            if bond < self.resolution_bond {
+                return Err(Error::DepositTooLow);
+            }
+
+            // the window is fixed when the proposal is made, later config changes don't move it
+            let proposed_at = self.env().block_timestamp();
+            let proposal = ResolutionProposal {
+                event_id,
+                proposer: caller,
+                outcome_id,
+                bond,
+                proposed_at,
+                dispute_deadline: proposed_at + self.dispute_window,
+                disputer: None,
+                dispute_bond: 0,
+            };
+            market.status = MarketStatus::Proposed;
+
+            self.event_to_resolution.insert(event_id, &proposal);
+            self.event_markets.insert(event_id, &market);
+
+            self.env().emit_event(ResolutionProposed {
+                event_id,
+                proposer: caller,
+                outcome_id,
+                bond,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        fn dispute_resolution(&mut self, event_id: EventId) -> Result<()> {
+            let caller = self.env().caller();
+            let bond = self.env().transferred_value();
+            let mut market = self.get_event_by_id(event_id)?.1;
+
+            if market.status != MarketStatus::Proposed {
+                return Err(Error::NotProposed);
+            }
+            let mut proposal = self.get_resolution_by_id(event_id)?;
+            if self.env().block_timestamp() >= proposal.dispute_deadline {
+                return Err(Error::DisputeWindowClosed);
+            }
+            if caller == proposal.proposer {
+                return Err(Error::NotOwner);
+            }
+            if bond < proposal.bond {
+                return Err(Error::DepositTooLow);
+            }
+
+            proposal.disputer = Some(caller);
+            proposal.dispute_bond = bond;
+            market.status = MarketStatus::Disputed;
+
+            self.event_to_resolution.insert(event_id, &proposal);
+            self.event_markets.insert(event_id, &market);
+
+            self.env().emit_event(ResolutionDisputed {
+                event_id,
+                disputer: caller,
+                bond,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn settle_dispute(&mut self, event_id: EventId, winner: Option<OutComeId>) -> Result<()> {
+            if self.arbitrator != Some(self.env().caller()) {
+                return Err(Error::NotOwner);
+            }
+
+            let mut market = self.get_event_by_id(event_id)?.1;
+            if market.status != MarketStatus::Disputed {
+                return Err(Error::NotDisputed);
+            }
+            let proposal = self.get_resolution_by_id(event_id)?;
+            let disputer = proposal.disputer.ok_or(Error::NotDisputed)?;
+
+            // the losing side's bond is slashed to the winning side
+            let (bond_winner, slashed_bond) = if winner == Some(proposal.outcome_id) {
+                (proposal.proposer, proposal.dispute_bond)
+            } else {
+                (disputer, proposal.bond)
+            };
+
+            match winner {
+                Some(outcome_id) => self.resolve_or_invalidate(event_id, outcome_id)?,
+                None => {
+                    market.status = MarketStatus::Invalid;
+                    self.event_markets.insert(event_id, &market);
+                    self.env().emit_event(MarketCancelled {
+                        event_id,
+                        cancelled_by: self.env().caller(),
+                    });
+                }
+            }
+
+            self.env()
+                .transfer(bond_winner, proposal.bond + proposal.dispute_bond)
+                .map_err(|_| Error::TransferFailed)?;
+
+            self.env().emit_event(DisputeSettled {
+                event_id,
+                bond_winner,
+                winner,
+                slashed_bond,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn finalize_resolution(&mut self, event_id: EventId) -> Result<()> {
+            let market = self.get_event_by_id(event_id)?.1;
+            if market.status != MarketStatus::Proposed {
+                return Err(Error::NotProposed);
+            }
+            let proposal = self.get_resolution_by_id(event_id)?;
+            if self.env().block_timestamp() < proposal.dispute_deadline {
+                return Err(Error::DisputeWindowOpen);
+            }
+
+            self.resolve_or_invalidate(event_id, proposal.outcome_id)?;
+
+            self.env()
+                .transfer(proposal.proposer, proposal.bond)
+                .map_err(|_| Error::TransferFailed)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn resolve_from_oracle(&mut self, event_id: EventId) -> Result<OutComeId> {
+            let market = self.get_event_by_id(event_id)?.1;
+            if market.scalar.is_some() {
+                return Err(Error::ResolverMismatch);
+            }
+            let (binding, answer) = self.get_oracle_answer(&market)?;
+
+            // the outcome with the greatest threshold not above the answer wins
+            let winner = binding
+                .answers
+                .iter()
+                .filter(|(threshold, _)| *threshold <= answer)
+                .max_by_key(|(threshold, _)| *threshold)
+                .map(|(_, outcome_id)| *outcome_id)
+                .ok_or(Error::InvalidOracle)?;
+
+            self.resolve_or_invalidate(event_id, winner)?;
+
+            Ok(winner)
+        }
+
+        #[ink(message)]
+        fn resolve_scalar_from_oracle(&mut self, event_id: EventId) -> Result<OracleAnswer> {
+            let market = self.get_event_by_id(event_id)?.1;
+            if market.scalar.is_none() {
+                return Err(Error::ResolverMismatch);
+            }
+            let (_, answer) = self.get_oracle_answer(&market)?;
+
+            self.resolve_scalar_event(event_id, answer)?;
+
+            Ok(answer)
+        }
+
+        #[ink(message)]
+        fn vote_resolution(&mut self, event_id: EventId, outcome_id: OutComeId) -> Result<()> {
+            let caller = self.env().caller();
+            let market = self.get_event_by_id(event_id)?.1;
+            let outcome = self.get_outcome_by_id(outcome_id)?;
+            let committee = self
+                .event_to_committee
+                .get(event_id)
+                .ok_or(Error::ResolverMismatch)?;
+
+            if !committee.members.contains(&caller) {
+                return Err(Error::NotCommitteeMember);
+            }
+            if self.env().block_timestamp() < market.resolve_date
+                || self.env().block_timestamp()
+                    >= market.resolve_date.saturating_add(COMMITTEE_TIMEOUT)
+                || market.status != MarketStatus::Open
+            {
+                return Err(Error::ResolveDateNotMatch);
+            }
+            if outcome.0.event_id != event_id {
+                return Err(Error::WrongEventOutCome);
+            }
+
+            self.event_member_to_vote
+                .insert((event_id, caller), &outcome_id);
+            self.env().emit_event(ResolutionVoted {
+                event_id,
+                member: caller,
+                outcome_id,
+            });
+
+            let votes = self
+                .get_vote_tally(event_id)?
+                .into_iter()
+                .find(|tally| tally.0 == outcome_id)
+                .map(|tally| tally.1)
+                .unwrap_or_default();
+            if votes < committee.threshold {
+                return Ok(());
+            }
+
+            self.resolve_or_invalidate(event_id, outcome_id)
+        }
+
+        #[ink(message)]
+        fn set_resolution_config(
+            &mut self,
+            arbitrator: AccountId,
+            resolution_bond: Balance,
+            dispute_window: Timestamp,
+        ) -> Result<()> {
+            if self.admin != Some(self.env().caller()) {
+                return Err(Error::NotOwner);
+            }
+
+            self.arbitrator = Some(arbitrator);
+            self.resolution_bond = resolution_bond;
+            self.dispute_window = dispute_window;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn get_resolution(&self, event_id: EventId) -> Result<ResolutionProposal> {
+            self.get_resolution_by_id(event_id)
+        }
+
+        #[ink(message)]
+        fn get_oracle_binding(&self, event_id: EventId) -> Result<Option<OracleBinding>> {
+            let _ = self.get_event_by_id(event_id)?;
+            Ok(self.event_to_oracle.get(event_id))
+        }
+
+        #[ink(message)]
+        fn get_committee(&self, event_id: EventId) -> Result<Option<Committee>> {
+            let _ = self.get_event_by_id(event_id)?;
+            Ok(self.event_to_committee.get(event_id))
+        }
+
+        #[ink(message)]
+        fn get_resolution_votes(&self, event_id: EventId) -> Result<Vec<(OutComeId, u32)>> {
+            self.get_vote_tally(event_id)
+        }
+
+        #[ink(message)]
+        fn get_resolution_config(&self) -> Result<(Option<AccountId>, Balance, Timestamp)> {
+            Ok((self.arbitrator, self.resolution_bond, self.dispute_window))
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        // Control passes with the trader's shares once the receiver holds 51% after the
+        // transfer, however many fills it took; otherwise the trader must keep 51% themselves.
+        fn update_trader(
+            &self,
+            fund: &mut InvestmentFund,
+            from: AccountId,
+            from_share: Share,
+            to: AccountId,
+            to_share: Share,
+        ) -> Result<()> {
+            if from == fund.trader {
+                if to_share * 100 / fund.total_share >= 51 {
+                    fund.trader = to;
+                } else if from_share * 100 / fund.total_share < 51 {
+                    return Err(Error::TraderNotIdentitied);
+                }
+            }
+            Ok(())
+        }
+
+        fn get_event_by_id(&self, event_id: EventId) -> Result<(Event, EventMarket, Supply)> {
+            let event = self.events.get(event_id);
+            let event_markets = self.event_markets.get(event_id);
+            if event.is_none() || event_markets.is_none() {
+                return Err(Error::EventNotFound);
+            }
+            let mut total_supply = 0;
+            for oid in self
+                .event_to_outcomes
+                .get(event_id)
+                .unwrap_or_default()
+                .into_iter()
+            {
+                total_supply += self.get_outcome_by_id(oid)?.0.total_supply;
+            }
+            Ok((event.unwrap(), event_markets.unwrap(), total_supply))
+        }
+
+        fn get_resolution_by_id(&self, event_id: EventId) -> Result<ResolutionProposal> {
+            let proposal = self.event_to_resolution.get(event_id);
+            if proposal.is_none() {
+                return Err(Error::NotProposed);
+            }
+            Ok(proposal.unwrap())
+        }
+
+        fn get_vote_tally(&self, event_id: EventId) -> Result<Vec<(OutComeId, u32)>> {
+            let mut rs: Vec<(OutComeId, u32)> = self
+                .event_to_outcomes
+                .get(event_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|outcome_id| (outcome_id, 0))
+                .collect();
+
+            let committee = self.event_to_committee.get(event_id);
+            if committee.is_none() {
+                return Ok(rs);
+            }
+            for member in committee.unwrap().members {
+                if let Some(vote) = self.event_member_to_vote.get((event_id, member)) {
+                    if let Some(tally) = rs.iter_mut().find(|tally| tally.0 == vote) {
+                        tally.1 += 1;
+                    }
+                }
+            }
+            Ok(rs)
+        }
+
+        fn get_outcome_by_id(&self, outcome_id: OutComeId) -> Result<(OutCome, MarketOutCome)> {
+            let outcome = self.outcomes.get(outcome_id);
+            let market_outcome = self.market_outcomes.get(outcome_id);
+            if outcome.is_none() || market_outcome.is_none() {
+                return Err(Error::WrongEventOutCome);
+            }
+            Ok((outcome.unwrap(), market_outcome.unwrap()))
+        }
+
+        fn get_fund_by_id(&self, fund_id: InvestmentFundId) -> Result<InvestmentFund> {
+            let fund = self.investment_funds.get(fund_id);
+            if fund.is_none() {
+                return Err(Error::FundNotFound);
+            }
+            Ok(fund.unwrap())
+        }
+
+        fn get_trade_by_id(&self, trade_id: TradeId) -> Result<FundTrade> {
+            let trade = self.fund_trades.get(trade_id);
+            if trade.is_none() {
+                return Err(Error::TradeNotFound);
+            }
+            Ok(trade.unwrap())
+        }
+
+        fn get_trade_status(&self, trade: &FundTrade) -> TradeStatus {
+            if trade.is_completed {
+                TradeStatus::Completed
+            } else if trade.is_cancelled {
+                TradeStatus::Cancelled
+            } else if self.env().block_timestamp() > trade.close_time {
+                TradeStatus::Expired
+            } else {
+                TradeStatus::Open
+            }
+        }
+
+        fn get_outcome_funds(
+            &self,
+            outcome_id: OutComeId,
+        ) -> Result<Vec<(InvestmentFund, Supply)>> {
+            let mut rs = Vec::new();
+            for i in self.outcome_to_funds.get(outcome_id).unwrap_or_default() {
+                rs.push((
+                    self.investment_funds.get(i).unwrap(),
+                    self.get_position(outcome_id, i).supply,
+                ));
+            }
+            Ok(rs)
+        }
+
+        fn get_fund_outcomes(&self, fund_id: InvestmentFundId) -> Result<Vec<(OutCome, Supply)>> {
+            let mut rs = Vec::new();
+            for oid in self.fund_to_outcomes.get(fund_id).unwrap_or_default() {
+                rs.push((
+                    self.outcomes.get(oid).unwrap(),
+                    self.get_position(oid, fund_id).supply,
+                ));
+            }
+            Ok(rs)
+        }
+
+        fn get_event_outcomes(&self, event_id: EventId) -> Result<Vec<(OutCome, MarketOutCome)>> {
+            let mut rs = Vec::new();
+            for i in self.event_to_outcomes.get(event_id).unwrap_or_default() {
+                rs.push(self.get_outcome_by_id(i)?);
+            }
+            Ok(rs)
+        }
+
+        fn get_outstanding_cost(&self, event_id: EventId) -> Result<Balance> {
+            let mut rs = 0;
+            for outcome_id in self.event_to_outcomes.get(event_id).unwrap_or_default() {
+                for fund_id in self.outcome_to_funds.get(outcome_id).unwrap_or_default() {
+                    rs += self.get_position(outcome_id, fund_id).cost_basis;
+                }
+                rs += self
+                    .outcome_to_claimed
+                    .get(outcome_id)
+                    .unwrap_or_default()
+                    .cost_basis;
+            }
+            Ok(rs)
+        }
+
+        fn get_outcome_quantities(&self, event_id: EventId) -> Result<Vec<(OutCome, Supply)>> {
+            let mut rs = Vec::new();
+            for outcome in self.get_event_outcomes(event_id)? {
+                let used_supply = outcome.0.total_supply - outcome.1.available_supply;
+                rs.push((outcome.0, used_supply));
+            }
+            Ok(rs)
+        }
+
+        fn get_trade_value(
+            &self,
+            market: &EventMarket,
+            outcome: &OutCome,
+            supplies: Supply,
+            is_buy: bool,
+        ) -> Result<Balance> {
+            match market.pricing {
+                PricingMode::Fixed => Ok(supplies as Balance * outcome.deposit_per_supply),
+                PricingMode::Lmsr {
+                    liquidity,
+                    payout_per_supply,
+                } => {
+                    let quantities = self.get_outcome_quantities(market.event_id)?;
+                    let current: Vec<Supply> = quantities.iter().map(|q| q.1).collect();
+                    let mut traded = current.clone();
+                    for (i, q) in quantities.iter().enumerate() {
+                        if q.0.outcome_id == outcome.outcome_id {
+                            traded[i] = if is_buy {
+                                q.1 + supplies
+                            } else {
+                                q.1.checked_sub(supplies).ok_or(Error::NotEnoughSupply)?
+                            };
+                        }
+                    }
+
+                    let cost_current =
+                        math::lmsr_cost(&current, liquidity).ok_or(Error::MathOverflow)?;
+                    let cost_traded =
+                        math::lmsr_cost(&traded, liquidity).ok_or(Error::MathOverflow)?;
+
+                    // buyers pay rounded up, sellers receive rounded down
+                    if is_buy {
+                        math::mul_div_up(
+                            cost_traded.saturating_sub(cost_current),
+                            payout_per_supply,
+                            math::ONE,
+                        )
+                    } else {
+                        math::mul_div(
+                            cost_current.saturating_sub(cost_traded),
+                            payout_per_supply,
+                            math::ONE,
+                        )
+                    }
+                    .ok_or(Error::MathOverflow)
+                }
+            }
+        }
+
+        // One unit of every outcome: what a single winning supply pays under LMSR, the sum of
+        // the fixed deposits otherwise.
+        fn get_set_price(
+            &self,
+            market: &EventMarket,
+            outcomes: &[(OutCome, MarketOutCome)],
+        ) -> Result<Balance> {
+            match market.pricing {
+                PricingMode::Fixed => Ok(outcomes
+                    .iter()
+                    .map(|(outcome, _)| outcome.deposit_per_supply)
+                    .sum()),
+                PricingMode::Lmsr {
+                    payout_per_supply, ..
+                } => Ok(payout_per_supply),
+            }
+        }
+
+        // Mark-to-market value of a position: implied probability times what a supply wins.
+        fn get_position_value(
+            &self,
+            outcome_id: OutComeId,
+            position: &Position,
+        ) -> Result<Balance> {
+            let outcome = self.get_outcome_by_id(outcome_id)?.0;
+            let market = self.get_event_by_id(outcome.event_id)?.1;
+
+            match market.status {
+                MarketStatus::Resolved => Ok(0),
+                MarketStatus::Invalid => Ok(position.cost_basis),
+                _ => {
+                    let (probability, prize_per_supply) =
+                        self.get_mark_price(&market, outcome_id)?;
+                    math::mul_div(
+                        probability,
+                        position.supply as Balance * prize_per_supply,
+                        PROBABILITY_ONE,
+                    )
+                    .ok_or(Error::MathOverflow)
+                }
+            }
+        }
+
+        fn get_mark_price(
+            &self,
+            market: &EventMarket,
+            outcome_id: OutComeId,
+        ) -> Result<(Probability, Balance)> {
+            let quantities = self.get_outcome_quantities(market.event_id)?;
+            let probability = self
+                .get_implied_probabilities(market, &quantities)?
+                .into_iter()
+                .find(|p| p.0 == outcome_id)
+                .map(|p| p.1)
+                .unwrap_or_default();
+            let prize_per_supply = match market.pricing {
+                PricingMode::Fixed => {
+                    let used_supply = quantities
+                        .iter()
+                        .find(|q| q.0.outcome_id == outcome_id)
+                        .map(|q| q.1)
+                        .unwrap_or_default();
+                    if used_supply == 0 {
+                        0
+                    } else {
+                        market.pool / used_supply as Balance
+                    }
+                }
+                PricingMode::Lmsr {
+                    payout_per_supply, ..
+                } => payout_per_supply,
+            };
+
+            Ok((probability, prize_per_supply))
+        }
+
+        fn get_fund_valuation(&self, fund: &InvestmentFund) -> Result<FundValuation> {
+            let mut positions = Vec::new();
+            let mut net_asset_value = fund.total_fund;
+
+            for outcome_id in self
+                .fund_to_outcomes
+                .get(fund.investment_fund_id)
+                .unwrap_or_default()
+            {
+                let outcome = self.get_outcome_by_id(outcome_id)?.0;
+                // prizes of resolved markets are already part of the liquid balance
+                if self.get_event_by_id(outcome.event_id)?.1.status == MarketStatus::Resolved {
+                    continue;
+                }
+
+                let position = self.get_position(outcome_id, fund.investment_fund_id);
+                let market_value = self.get_position_value(outcome_id, &position)?;
+                net_asset_value = net_asset_value
+                    .checked_add(market_value)
+                    .ok_or(Error::MathOverflow)?;
+
+                positions.push(PositionValuation {
+                    outcome_id,
+                    supply: position.supply,
+                    cost_basis: position.cost_basis,
+                    mark_price: market_value / position.supply.max(1) as Balance,
+                    market_value,
+                    unrealized_pnl: market_value as i128 - position.cost_basis as i128,
+                });
+            }
+
+            let nav_per_share = if fund.total_share == 0 {
+                0
+            } else {
+                net_asset_value / fund.total_share as Balance
+            };
+
+            Ok(FundValuation {
+                investment_fund_id: fund.investment_fund_id,
+                liquid_balance: fund.total_fund,
+                positions,
+                net_asset_value,
+                nav_per_share,
+            })
+        }
+
+        fn get_implied_probabilities(
+            &self,
+            market: &EventMarket,
+            quantities: &[(OutCome, Supply)],
+        ) -> Result<Vec<(OutComeId, Probability)>> {
+            let prices = match market.pricing {
+                PricingMode::Fixed => {
+                    let stakes: Vec<Balance> = quantities
+                        .iter()
+                        .map(|q| q.1 as Balance * q.0.deposit_per_supply)
+                        .collect();
+                    let total_stake: Balance = stakes.iter().sum();
+
+                    if total_stake == 0 {
+                        let count = quantities.len() as Probability;
+                        stakes.iter().map(|_| PROBABILITY_ONE / count).collect()
+                    } else {
+                        stakes
+                            .into_iter()
+                            .map(|stake| math::mul_div(stake, PROBABILITY_ONE, total_stake))
+                            .collect::<Option<Vec<Probability>>>()
+                            .ok_or(Error::MathOverflow)?
+                    }
+                }
+                PricingMode::Lmsr { liquidity, .. } => {
+                    let supplies: Vec<Supply> = quantities.iter().map(|q| q.1).collect();
+                    math::lmsr_prices(&supplies, liquidity).ok_or(Error::MathOverflow)?
+                }
+            };
+
+            Ok(quantities
+                .iter()
+                .zip(prices)
+                .map(|(q, price)| (q.0.outcome_id, price))
+                .collect())
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        fn new_outcome(&mut self, event_id: EventId, payload: OutComePayload) -> OutCome {
+            let outcome_id = self.next_outcome_id;
+            self.next_outcome_id += 1;
+            OutCome {
+                event_id,
+                outcome_id,
+                description: payload.description,
+                deposit_per_supply: payload.deposit_per_supply,
+                total_supply: payload.total_supply,
+            }
+        }
+
+        fn new_market_outcome(
+            &mut self,
+            event_id: EventId,
+            outcome_id: OutComeId,
+            supply: Supply,
+        ) -> MarketOutCome {
+            MarketOutCome {
+                event_id,
+                outcome_id,
+                available_supply: supply,
+            }
+        }
+
+        fn new_event(
+            &mut self,
+            owner: AccountId,
+            question: String,
+            bets: Vec<OutComePayload>,
+            metadata: EventMetadata,
+        ) -> Result<(Event, Vec<OutComeId>)> {
+            let mut event_bets: Vec<OutComeId> = Vec::new();
+
+            let event_id = self.next_event_id;
+            self.next_event_id += 1;
+
+            if bets.len() < 2 {
+                return Err(Error::AtLeastTwoOutcome);
+            }
+
+            for payload in bets.into_iter() {
+                let outcome_total_supply = payload.total_supply;
+
+                let new_outcome = self.new_outcome(event_id, payload);
+                let market_outcome =
+                    self.new_market_outcome(event_id, new_outcome.outcome_id, outcome_total_supply);
+
+                event_bets.push(new_outcome.outcome_id);
+                self.outcomes.insert(new_outcome.outcome_id, &new_outcome);
+                self.market_outcomes
+                    .insert(new_outcome.outcome_id, &market_outcome);
+            }
+
+            Ok((
+                Event {
+                    event_id,
+                    owner,
+                    question,
+                    metadata,
+                },
+                event_bets,
+            ))
+        }
+
+        fn new_market(
+            &mut self,
+            event_id: EventId,
+            deposit: Balance,
+            resolve_date: Timestamp,
+            terms: MarketTerms,
+            outcome_count: usize,
+        ) -> Result<EventMarket> {
+            let pricing = terms.pricing;
+            if let PricingMode::Lmsr {
+                liquidity,
+                payout_per_supply,
+            } = pricing
+            {
+                if liquidity == 0 || payout_per_supply == 0 {
+                    return Err(Error::InvalidPricing);
+                }
+                let max_loss = math::lmsr_max_loss(outcome_count, liquidity)
+                    .and_then(|loss| math::mul_div_up(loss, payout_per_supply, math::ONE))
+                    .ok_or(Error::MathOverflow)?;
+                if deposit < max_loss {
+                    return Err(Error::DepositTooLow);
+                }
+            }
+
+            let new_market = EventMarket {
+                event_id,
+                pool: deposit,
+                creation_deposit: deposit,
+                status: MarketStatus::Open,
+                resolve_date,
+                winning_outcome: None,
+                prize_per_supply: 0,
+                pricing,
+                collateral: terms.collateral,
+                scalar: None,
+            };
+
+            Ok(new_market)
+        }
+
+        fn new_oracle_binding(
+            &mut self,
+            payload: OracleBindingPayload,
+            outcomes: &[OutComeId],
+        ) -> Result<OracleBinding> {
+            if payload.answers.is_empty() {
+                return Err(Error::InvalidOracle);
+            }
+
+            let mut answers = Vec::new();
+            for (threshold, index) in payload.answers.into_iter() {
+                let outcome_id = outcomes.get(index as usize).ok_or(Error::InvalidOracle)?;
+                answers.push((threshold, *outcome_id));
+            }
+
+            Ok(OracleBinding {
+                oracle: payload.oracle,
+                query: payload.query,
+                answers,
+            })
+        }
+
+        fn new_scalar_market(
+            &self,
+            binding: &OracleBinding,
+            outcomes: &[OutComeId],
+        ) -> Result<ScalarMarket> {
+            if outcomes.len() != 2 {
+                return Err(Error::InvalidScalar);
+            }
+            let (short, long) = match binding.answers[..] {
+                [a, b] if a.0 < b.0 => (a, b),
+                [a, b] if a.0 > b.0 => (b, a),
+                _ => return Err(Error::InvalidScalar),
+            };
+            if short.1 == long.1 {
+                return Err(Error::InvalidScalar);
+            }
+
+            Ok(ScalarMarket {
+                lower: short.0,
+                upper: long.0,
+                long_outcome: long.1,
+                short_outcome: short.1,
+                resolved_value: None,
+                long_prize_per_supply: 0,
+                short_prize_per_supply: 0,
+            })
+        }
+
+        fn get_oracle_answer(&self, market: &EventMarket) -> Result<(OracleBinding, OracleAnswer)> {
+            let binding = self
+                .event_to_oracle
+                .get(market.event_id)
+                .ok_or(Error::ResolverMismatch)?;
+
+            if self.env().block_timestamp() < market.resolve_date
+                || market.status != MarketStatus::Open
+            {
+                return Err(Error::ResolveDateNotMatch);
+            }
+
+            let oracle: contract_ref!(Oracle, MyEnvironment) = binding.oracle.into();
+            let answer = oracle
+                .call()
+                .get_answer(binding.query.clone())
+                .try_invoke()
+                .map_err(|_| Error::OracleCallFailed)?
+                .map_err(|_| Error::OracleCallFailed)?
+                .ok_or(Error::OracleNotReady)?;
+
+            Ok((binding, answer))
+        }
+
+        fn validate_committee(&self, committee: &Committee) -> Result<()> {
+            if committee.threshold == 0 || committee.threshold as usize > committee.members.len() {
+                return Err(Error::InvalidCommittee);
+            }
+            for (i, member) in committee.members.iter().enumerate() {
+                if committee.members[i + 1..].contains(member) {
+                    return Err(Error::InvalidCommittee);
+                }
+            }
+            Ok(())
+        }
+
+        fn new_fund(
+            &mut self,
+            trader: AccountId,
+            total_share: Share,
+            total_fund: Balance,
+            metadata: FundMetadata,
+        ) -> InvestmentFund {
+            let investment_fund_id = self.next_fund_id;
+            self.next_fund_id += 1;
+
+            InvestmentFund {
+                investment_fund_id,
+                trader,
+                total_fund,
+                total_share,
+                metadata,
+                subscription: SubscriptionConfig {
+                    is_open: false,
+                    share_cap: None,
+                    min_ticket: 0,
+                },
+                fees: FundFees::default(),
+                is_dissolved: false,
+                distribution: DistributionPolicy::Reinvest,
+                dividend_per_share: 0,
+                fees_accrued_at: self.env().block_timestamp(),
+                high_water_mark: total_fund / total_share as Balance,
+                collateral: None,
+            }
+        }
+
+        fn new_trade(
+            &mut self,
+            fund_id: InvestmentFundId,
+            proponent: AccountId,
+            share: Share,
+            price_per_share: Balance,
+            min_fill: Share,
+            close_time: Timestamp,
+        ) -> FundTrade {
+            let trade_id = self.next_trade_id;
+            self.next_trade_id += 1;
+
+            FundTrade {
+                investment_fund_id: fund_id,
+                trade_id,
+                proponent,
+                proposed_person: None,
+                share,
+                remaining_share: share,
+                price_per_share,
+                min_fill,
+                fill_count: 0,
+                auction: None,
+                best_bid: None,
+                opened_at: self.env().block_timestamp(),
+                close_time,
+                is_completed: false,
+                is_cancelled: false,
+            }
+        }
+
+        fn insert_trade(&mut self, trade: &FundTrade) {
+            let mut fund_trades = self
+                .fund_to_trades
+                .get(trade.investment_fund_id)
+                .unwrap_or_default();
+            let mut proponent_trades = self
+                .proponent_to_trades
+                .get(trade.proponent)
+                .unwrap_or_default();
+
+            fund_trades.push(trade.trade_id);
+            proponent_trades.push(trade.trade_id);
+
+            self.fund_trades.insert(trade.trade_id, trade);
+            self.fund_to_trades
+                .insert(trade.investment_fund_id, &fund_trades);
+            self.proponent_to_trades
+                .insert(trade.proponent, &proponent_trades);
+        }
+
+        // Per share price a buyer pays right now: the fixed price, the decayed dutch price or
+        // the best english bid (the starting price before any bid).
+        fn get_current_price(&self, trade: &FundTrade) -> Result<Balance> {
+            match (trade.auction, &trade.best_bid) {
+                (Some(AuctionMode::English { .. }), Some(best_bid)) => Ok(best_bid.price_per_share),
+                (Some(AuctionMode::Dutch { floor_price }), _) => {
+                    let now = self.env().block_timestamp();
+                    if now >= trade.close_time {
+                        return Ok(floor_price);
+                    }
+                    let decay = math::mul_div(
+                        trade.price_per_share - floor_price,
+                        (now - trade.opened_at) as Balance,
+                        (trade.close_time - trade.opened_at) as Balance,
+                    )
+                    .ok_or(Error::MathOverflow)?;
+                    Ok(trade.price_per_share - decay)
+                }
+                _ => Ok(trade.price_per_share),
+            }
+        }
+
+        // Hands `shares` out of the trade's escrow to the buyer and pays the proponent `price`,
+        // which the caller has already collected.
+        fn fill_trade(
+            &mut self,
+            trade: &mut FundTrade,
+            buyer: AccountId,
+            shares: Share,
+            price: Balance,
+        ) -> Result<()> {
+            // escrowed shares are no longer part of the proponent's balance
+            let proponent_share = self
+                .fund_owner_to_shares
+                .get((trade.investment_fund_id, trade.proponent))
+                .unwrap_or_default();
+            let buyer_share = self
+                .fund_owner_to_shares
+                .get((trade.investment_fund_id, buyer))
+                .unwrap_or_default();
+
+            let mut fund = self.get_fund_by_id(trade.investment_fund_id)?;
+            let previous_trader = fund.trader;
+
+            // what is still in escrow after this fill counts towards the proponent
+            self.update_trader(
+                &mut fund,
+                trade.proponent,
+                proponent_share + trade.remaining_share - shares,
+                buyer,
+                buyer_share + shares,
+            )?;
+
+            trade.remaining_share -= shares;
+            trade.is_completed = trade.remaining_share == 0;
+            self.trade_fills.insert(
+                (trade.trade_id, trade.fill_count),
+                &TradeFill {
+                    buyer,
+                    share: shares,
+                    price,
+                    filled_at: self.env().block_timestamp(),
+                },
+            );
+            trade.fill_count += 1;
+
+            self.fund_trades.insert(trade.trade_id, trade);
+            self.set_share(trade.investment_fund_id, buyer, buyer_share + shares);
+            self.investment_funds
+                .insert(trade.investment_fund_id, &fund);
+
+            self.pay_collateral(fund.collateral, trade.proponent, price)?;
+
+            self.env().emit_event(SharesTransferred {
+                fund_id: trade.investment_fund_id,
+                from: trade.proponent,
+                to: buyer,
+                amount: shares,
+            });
+            self.env().emit_event(ProposalAccepted {
+                fund_id: trade.investment_fund_id,
+                buyer,
+                trade_id: trade.trade_id,
+                share: shares,
+                price,
+                remaining_share: trade.remaining_share,
+            });
+            if fund.trader != previous_trader {
+                self.env().emit_event(TraderChanged {
+                    fund_id: trade.investment_fund_id,
+                    new_trader: fund.trader,
+                    previous_trader,
+                });
+            }
+
+            Ok(())
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        // Pays the winning positions out of the pool; only runs once a resolution is final.
+        fn resolve_event(&mut self, event_id: EventId, winner: OutComeId) -> Result<()> {
+            let event = self.get_event_by_id(event_id)?;
+            let winning_outcome = self.get_outcome_by_id(winner)?;
+
+            if matches!(
+                event.1.status,
+                MarketStatus::Resolved | MarketStatus::Invalid
+            ) {
+                return Err(Error::ResolveDateNotMatch);
+            }
+            if winning_outcome.0.event_id != event_id {
+                return Err(Error::WrongEventOutCome);
+            }
+
+            let mut updated_market = event.1;
+            let used_supply = winning_outcome.0.total_supply - winning_outcome.1.available_supply;
+            if used_supply == 0 {
+                return Err(Error::NoBodyBetted);
+            }
+            let prize_per_supply = match updated_market.pricing {
+                PricingMode::Fixed => updated_market.pool / (used_supply as Balance),
+                PricingMode::Lmsr {
+                    payout_per_supply, ..
+                } => payout_per_supply,
+            };
+
+            updated_market.prize_per_supply = prize_per_supply;
+            updated_market.winning_outcome = Some(winner);
+
+            self.settle_market(event.0.owner, updated_market, &[(winner, prize_per_supply)])?;
+
+            self.env().emit_event(MarketResolved {
+                event_id,
+                winning_outcome: winner,
+                prize_per_supply,
+            });
+
+            Ok(())
+        }
+
+        // A final winner nobody betted on can't pay out, so the event turns invalid and every
+        // stake is refunded at cost basis instead.
+        fn resolve_or_invalidate(&mut self, event_id: EventId, winner: OutComeId) -> Result<()> {
+            let outcome = self.get_outcome_by_id(winner)?;
+            if outcome.0.event_id != event_id {
+                return Err(Error::WrongEventOutCome);
+            }
+            if outcome.0.total_supply != outcome.1.available_supply {
+                return self.resolve_event(event_id, winner);
+            }
+
+            let mut market = self.get_event_by_id(event_id)?.1;
+            market.status = MarketStatus::Invalid;
+            self.event_markets.insert(event_id, &market);
+            self.env().emit_event(MarketCancelled {
+                event_id,
+                cancelled_by: self.env().caller(),
+            });
+
+            Ok(())
+        }
+
+        // Splits the pool between LONG and SHORT by where the value falls in the range.
+        fn resolve_scalar_event(&mut self, event_id: EventId, value: OracleAnswer) -> Result<()> {
+            let (event, mut updated_market, _) = self.get_event_by_id(event_id)?;
+            let mut scalar = updated_market.scalar.ok_or(Error::InvalidScalar)?;
+
+            if matches!(
+                updated_market.status,
+                MarketStatus::Resolved | MarketStatus::Invalid
+            ) {
+                return Err(Error::ResolveDateNotMatch);
+            }
+
+            let long_outcome = self.get_outcome_by_id(scalar.long_outcome)?;
+            let short_outcome = self.get_outcome_by_id(scalar.short_outcome)?;
+            let long_used = long_outcome.0.total_supply - long_outcome.1.available_supply;
+            let short_used = short_outcome.0.total_supply - short_outcome.1.available_supply;
+            // with nobody on either side there is nothing to split, the market refunds instead
+            if long_used == 0 && short_used == 0 {
+                updated_market.status = MarketStatus::Invalid;
+                self.event_markets.insert(event_id, &updated_market);
+                self.env().emit_event(MarketCancelled {
+                    event_id,
+                    cancelled_by: self.env().caller(),
+                });
+                return Ok(());
+            }
+
+            let clamped = value.clamp(scalar.lower, scalar.upper);
+            let long_ratio = math::mul_div(
+                clamped - scalar.lower,
+                PROBABILITY_ONE,
+                scalar.upper - scalar.lower,
+            )
+            .ok_or(Error::MathOverflow)?;
+
+            let (long_prize_per_supply, short_prize_per_supply) = match updated_market.pricing {
+                // a side nobody holds forfeits its share, which settlement hands back to the owner
+                PricingMode::Fixed => {
+                    let long_pool = math::mul_div(updated_market.pool, long_ratio, PROBABILITY_ONE)
+                        .ok_or(Error::MathOverflow)?;
+                    let short_pool = updated_market.pool - long_pool;
+                    (
+                        long_pool
+                            .checked_div(long_used as Balance)
+                            .unwrap_or_default(),
+                        short_pool
+                            .checked_div(short_used as Balance)
+                            .unwrap_or_default(),
+                    )
+                }
+                PricingMode::Lmsr {
+                    payout_per_supply, ..
+                } => {
+                    let long_prize = math::mul_div(payout_per_supply, long_ratio, PROBABILITY_ONE)
+                        .ok_or(Error::MathOverflow)?;
+                    (long_prize, payout_per_supply - long_prize)
+                }
+            };
+
+            scalar.resolved_value = Some(value);
+            scalar.long_prize_per_supply = long_prize_per_supply;
+            scalar.short_prize_per_supply = short_prize_per_supply;
+            updated_market.scalar = Some(scalar);
+
+            let payouts: Vec<(OutComeId, Balance)> = [
+                (scalar.long_outcome, long_prize_per_supply),
+                (scalar.short_outcome, short_prize_per_supply),
+            ]
+            .into_iter()
+            .filter(|(_, prize_per_supply)| *prize_per_supply > 0)
+            .collect();
+
+            self.settle_market(event.owner, updated_market, &payouts)?;
+
+            self.env().emit_event(ScalarResolved {
+                event_id,
+                value,
+                long_prize_per_supply,
+                short_prize_per_supply,
+            });
+
+            Ok(())
+        }
+
+        // Credits the funds holding each paying outcome and closes the market.
+        fn settle_market(
+            &mut self,
+            owner: AccountId,
+            mut updated_market: EventMarket,
+            payouts: &[(OutComeId, Balance)],
+        ) -> Result<()> {
+            let event_id = updated_market.event_id;
+            let mut updated_funds: Vec<(InvestmentFund, Balance)> = Vec::new();
+            let mut reserved: Balance = 0;
+
+            for (outcome_id, prize_per_supply) in payouts.iter() {
+                for (fund, supply_holding) in self.get_outcome_funds(*outcome_id)? {
+                    if supply_holding == 0 {
+                        return Err(Error::SomethingWrong);
+                    }
+
+                    let prize = supply_holding as Balance * prize_per_supply;
+                    updated_market.pool -= prize;
+
+                    // a fund holding both sides of a scalar event is credited once
+                    match updated_funds
+                        .iter_mut()
+                        .find(|f| f.0.investment_fund_id == fund.investment_fund_id)
+                    {
+                        Some(updated) => {
+                            updated.0.total_fund += prize;
+                            updated.1 += prize;
+                        }
+                        None => {
+                            let mut fund = fund;
+                            fund.total_fund += prize;
+                            updated_funds.push((fund, prize));
+                        }
+                    }
+                }
+
+                let claimed = self.outcome_to_claimed.get(outcome_id).unwrap_or_default();
+                reserved += claimed.supply as Balance * prize_per_supply;
+            }
+
+            if matches!(updated_market.pricing, PricingMode::Lmsr { .. })
+                || updated_market.scalar.is_some()
+            {
+                // keep what deferred redemption claims on the paying outcomes are owed
+                let remainder = updated_market.pool.saturating_sub(reserved);
+                self.pay_collateral(updated_market.collateral, owner, remainder)?;
+                updated_market.pool -= remainder;
+            }
+
+            updated_market.status = MarketStatus::Resolved;
+
+            self.event_markets.insert(event_id, &updated_market);
+            for (fund, _) in updated_funds.iter() {
+                self.investment_funds.insert(fund.investment_fund_id, fund);
+            }
+
+            // fees and distributions value the funds with this market already resolved
+            for (mut fund, prize) in updated_funds {
+                // a fee that can't be computed mustn't block the resolution; the fund is left as
+                // it was and the next accrual charges the whole period
+                let (fees_accrued_at, high_water_mark, total_fund) =
+                    (fund.fees_accrued_at, fund.high_water_mark, fund.total_fund);
+                if self.accrue_fees(&mut fund, true).is_err() {
+                    fund.fees_accrued_at = fees_accrued_at;
+                    fund.high_water_mark = high_water_mark;
+                    fund.total_fund = total_fund;
+                }
+
+                let payable = prize.min(fund.total_fund);
+                let distributed = self.distribute_dividends(&mut fund, payable);
+                if distributed > 0 {
+                    fund.total_fund -= distributed;
+                    // paying out isn't a loss the trader has to make up before earning again
+                    fund.high_water_mark = fund
+                        .high_water_mark
+                        .saturating_sub(distributed / fund.total_share as Balance);
+                    self.env().emit_event(DividendsDistributed {
+                        fund_id: fund.investment_fund_id,
+                        event_id,
+                        amount: distributed,
+                        dividend_per_share: fund.dividend_per_share,
+                    });
+                }
+
+                self.investment_funds.insert(fund.investment_fund_id, &fund);
+            }
+
+            Ok(())
+        }
+
+        // What a redeemed supply of the outcome is paid once its market resolved.
+        fn get_prize_per_supply(&self, market: &EventMarket, outcome_id: OutComeId) -> Balance {
+            match market.scalar {
+                Some(scalar) if scalar.long_outcome == outcome_id => scalar.long_prize_per_supply,
+                Some(scalar) if scalar.short_outcome == outcome_id => scalar.short_prize_per_supply,
+                Some(_) => 0,
+                None if market.winning_outcome == Some(outcome_id) => market.prize_per_supply,
+                None => 0,
+            }
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        fn get_position(&self, outcome_id: OutComeId, fund_id: InvestmentFundId) -> Position {
+            self.outcome_fund_to_positions
+                .get((outcome_id, fund_id))
+                .unwrap_or_default()
+        }
+
+        fn increase_position(
+            &mut self,
+            outcome_id: OutComeId,
+            fund_id: InvestmentFundId,
+            supplies: Supply,
+            cost: Balance,
+        ) {
+            let mut position = self.get_position(outcome_id, fund_id);
+
+            if position.supply == 0 {
+                let mut funds_of_outcome =
+                    self.outcome_to_funds.get(outcome_id).unwrap_or_default();
+                let mut outcomes_of_fund = self.fund_to_outcomes.get(fund_id).unwrap_or_default();
+                if !funds_of_outcome.contains(&fund_id) {
+                    funds_of_outcome.push(fund_id);
+                    self.outcome_to_funds.insert(outcome_id, &funds_of_outcome);
+                }
+                if !outcomes_of_fund.contains(&outcome_id) {
+                    outcomes_of_fund.push(outcome_id);
+                    self.fund_to_outcomes.insert(fund_id, &outcomes_of_fund);
+                }
+            }
+
+            position.supply += supplies;
+            position.cost_basis += cost;
+            self.outcome_fund_to_positions
+                .insert((outcome_id, fund_id), &position);
+        }
+
+        // Releases `supplies` from the position and returns the proportional cost basis.
+        fn decrease_position(
+            &mut self,
+            outcome_id: OutComeId,
+            fund_id: InvestmentFundId,
+            supplies: Supply,
+        ) -> Result<Balance> {
+            let mut position = self.get_position(outcome_id, fund_id);
+            if position.supply < supplies {
+                return Err(Error::NotEnoughSupply);
+            }
+
+            let released_cost = math::mul_div(
+                position.cost_basis,
+                supplies as Balance,
+                position.supply as Balance,
+            )
+            .ok_or(Error::MathOverflow)?;
+            position.supply -= supplies;
+            position.cost_basis -= released_cost;
+
+            if position.supply == 0 {
+                let mut funds_of_outcome =
+                    self.outcome_to_funds.get(outcome_id).unwrap_or_default();
+                let mut outcomes_of_fund = self.fund_to_outcomes.get(fund_id).unwrap_or_default();
+                funds_of_outcome.retain(|id| *id != fund_id);
+                outcomes_of_fund.retain(|id| *id != outcome_id);
+
+                self.outcome_fund_to_positions.remove((outcome_id, fund_id));
+                self.outcome_to_funds.insert(outcome_id, &funds_of_outcome);
+                self.fund_to_outcomes.insert(fund_id, &outcomes_of_fund);
+            } else {
+                self.outcome_fund_to_positions
+                    .insert((outcome_id, fund_id), &position);
+            }
+
+            Ok(released_cost)
+        }
+
+        fn increase_claim(
+            &mut self,
+            outcome_id: OutComeId,
+            owner: AccountId,
+            supplies: Supply,
+            cost: Balance,
+        ) {
+            let mut claim = self
+                .outcome_owner_to_claims
+                .get((outcome_id, owner))
+                .unwrap_or_default();
+            let mut claimed = self.outcome_to_claimed.get(outcome_id).unwrap_or_default();
+
+            if claim.supply == 0 {
+                let mut outcomes_of_owner =
+                    self.owner_to_claim_outcomes.get(owner).unwrap_or_default();
+                if !outcomes_of_owner.contains(&outcome_id) {
+                    outcomes_of_owner.push(outcome_id);
+                    self.owner_to_claim_outcomes
+                        .insert(owner, &outcomes_of_owner);
+                }
+            }
+
+            claim.supply += supplies;
+            claim.cost_basis += cost;
+            claimed.supply += supplies;
+            claimed.cost_basis += cost;
+
+            self.outcome_owner_to_claims
+                .insert((outcome_id, owner), &claim);
+            self.outcome_to_claimed.insert(outcome_id, &claimed);
+        }
+
+        fn decrease_claim(
+            &mut self,
+            outcome_id: OutComeId,
+            owner: AccountId,
+            supplies: Supply,
+        ) -> Result<Balance> {
+            let mut claim = self
+                .outcome_owner_to_claims
+                .get((outcome_id, owner))
+                .unwrap_or_default();
+            let mut claimed = self.outcome_to_claimed.get(outcome_id).unwrap_or_default();
+            if claim.supply < supplies {
+                return Err(Error::NotEnoughSupply);
+            }
+
+            let released_cost = math::mul_div(
+                claim.cost_basis,
+                supplies as Balance,
+                claim.supply as Balance,
+            )
+            .ok_or(Error::MathOverflow)?;
+            claim.supply -= supplies;
+            claim.cost_basis -= released_cost;
+            claimed.supply -= supplies;
+            claimed.cost_basis -= released_cost;
+            self.outcome_to_claimed.insert(outcome_id, &claimed);
+
+            if claim.supply == 0 {
+                let mut outcomes_of_owner =
+                    self.owner_to_claim_outcomes.get(owner).unwrap_or_default();
+                outcomes_of_owner.retain(|id| *id != outcome_id);
+
+                self.outcome_owner_to_claims.remove((outcome_id, owner));
+                self.owner_to_claim_outcomes
+                    .insert(owner, &outcomes_of_owner);
+            } else {
+                self.outcome_owner_to_claims
+                    .insert((outcome_id, owner), &claim);
+            }
+
+            Ok(released_cost)
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        fn new_order(
+            &mut self,
+            fund_id: InvestmentFundId,
+            owner: AccountId,
+            side: OrderSide,
+            share: Share,
+            price_per_share: Balance,
+        ) -> Order {
+            let order_id = self.next_order_id;
+            self.next_order_id += 1;
+
+            let mut orders_of_owner = self.owner_to_orders.get(owner).unwrap_or_default();
+            orders_of_owner.push(order_id);
+            self.owner_to_orders.insert(owner, &orders_of_owner);
+
+            Order {
+                order_id,
+                investment_fund_id: fund_id,
+                owner,
+                side,
+                price_per_share,
+                share,
+                remaining_share: share,
+                placed_at: self.env().block_timestamp(),
+                is_cancelled: false,
+            }
+        }
+
+        fn get_order_by_id(&self, order_id: OrderId) -> Result<Order> {
+            let order = self.orders.get(order_id);
+            if order.is_none() {
+                return Err(Error::OrderNotFound);
+            }
+            Ok(order.unwrap())
+        }
+
+        // Orders too small to be worth their storage would let anyone fill the book for free.
+        fn validate_order(&self, shares: Share, price_per_share: Balance) -> Result<Balance> {
+            if shares == 0 || price_per_share == 0 {
+                return Err(Error::InvalidOrder);
+            }
+            let value = (shares as Balance)
+                .checked_mul(price_per_share)
+                .ok_or(Error::MathOverflow)?;
+            if value < MIN_ORDER_VALUE {
+                return Err(Error::InvalidOrder);
+            }
+            Ok(value)
+        }
+
+        // Bids are kept best (highest) price first, asks lowest price first; equal prices keep
+        // their arrival order.
+        fn get_book_side(&self, fund_id: InvestmentFundId, side: OrderSide) -> Vec<OrderId> {
+            let mut book = Vec::new();
+            let mut next = self.book_heads.get((fund_id, side));
+            while let Some(order_id) = next {
+                book.push(order_id);
+                next = self.next_in_book.get(order_id);
+            }
+            book
+        }
+
+        // Links `order_id` in after `previous`, or at the head of the book.
+        fn link_order(
+            &mut self,
+            fund_id: InvestmentFundId,
+            side: OrderSide,
+            previous: Option<OrderId>,
+            order_id: OrderId,
+        ) {
+            let next = match previous {
+                Some(previous) => self.next_in_book.get(previous),
+                None => self.book_heads.get((fund_id, side)),
+            };
+            if let Some(next) = next {
+                self.next_in_book.insert(order_id, &next);
+            }
+            match previous {
+                Some(previous) => self.next_in_book.insert(previous, &order_id),
+                None => self.book_heads.insert((fund_id, side), &order_id),
+            };
+            let size = self.book_sizes.get((fund_id, side)).unwrap_or_default();
+            self.book_sizes.insert((fund_id, side), &(size + 1));
+        }
+
+        // Unlinks `order_id`, which follows `previous`, or is the head of the book.
+        fn unlink_order(
+            &mut self,
+            fund_id: InvestmentFundId,
+            side: OrderSide,
+            previous: Option<OrderId>,
+            order_id: OrderId,
+        ) {
+            let next = self.next_in_book.get(order_id);
+            self.next_in_book.remove(order_id);
+            match (previous, next) {
+                (Some(previous), Some(next)) => self.next_in_book.insert(previous, &next),
+                (Some(previous), None) => {
+                    self.next_in_book.remove(previous);
+                    None
+                }
+                (None, Some(next)) => self.book_heads.insert((fund_id, side), &next),
+                (None, None) => {
+                    self.book_heads.remove((fund_id, side));
+                    None
+                }
+            };
+            let size = self.book_sizes.get((fund_id, side)).unwrap_or_default();
+            self.book_sizes
+                .insert((fund_id, side), &size.saturating_sub(1));
+        }
+
+        fn remove_from_book(&mut self, order: &Order) {
+            let mut previous = None;
+            for order_id in self.get_book_side(order.investment_fund_id, order.side) {
+                if order_id == order.order_id {
+                    self.unlink_order(order.investment_fund_id, order.side, previous, order_id);
+                    return;
+                }
+                previous = Some(order_id);
+            }
+        }
+
+        fn get_book_levels(
+            &self,
+            fund_id: InvestmentFundId,
+            side: OrderSide,
+            depth: u32,
+        ) -> Result<Vec<OrderBookLevel>> {
+            let mut levels: Vec<OrderBookLevel> = Vec::new();
+
+            for order_id in self.get_book_side(fund_id, side).into_iter() {
+                let order = self.get_order_by_id(order_id)?;
+                match levels.last_mut() {
+                    Some(level) if level.price_per_share == order.price_per_share => {
+                        level.share += order.remaining_share;
+                        level.orders += 1;
+                    }
+                    _ => {
+                        if levels.len() as u32 == depth {
+                            break;
+                        }
+                        levels.push(OrderBookLevel {
+                            price_per_share: order.price_per_share,
+                            share: order.remaining_share,
+                            orders: 1,
+                        });
+                    }
+                }
+            }
+
+            Ok(levels)
+        }
+
+        // Stores the order and rests whatever is left of it on its side of the book, which
+        // holds at most MAX_BOOK_ORDERS so walking it stays bounded; a full side drops its worst
+        // order for a better one.
+        fn save_order(&mut self, order: &Order) -> Result<()> {
+            self.orders.insert(order.order_id, order);
+            if order.remaining_share == 0 {
+                return Ok(());
+            }
+
+            let fund_id = order.investment_fund_id;
+            if self
+                .book_sizes
+                .get((fund_id, order.side))
+                .unwrap_or_default()
+                >= MAX_BOOK_ORDERS
+            {
+                self.evict_worst_order(order)?;
+            }
+
+            let mut previous = None;
+            for order_id in self.get_book_side(fund_id, order.side) {
+                let resting = self.get_order_by_id(order_id)?;
+                let is_worse = match order.side {
+                    OrderSide::Bid => resting.price_per_share < order.price_per_share,
+                    OrderSide::Ask => resting.price_per_share > order.price_per_share,
+                };
+                if is_worse {
+                    break;
+                }
+                previous = Some(order_id);
+            }
+            self.link_order(fund_id, order.side, previous, order.order_id);
+            Ok(())
+        }
+
+        // Makes room on a full side for a better priced order by cancelling the worst resting
+        // one, so a book can't be locked with cheap orders nobody will fill.
+        fn evict_worst_order(&mut self, order: &Order) -> Result<()> {
+            let book = self.get_book_side(order.investment_fund_id, order.side);
+            let (previous, worst_id) = match book[..] {
+                [.., previous, worst] => (Some(previous), worst),
+                [worst] => (None, worst),
+                [] => return Err(Error::OrderBookFull),
+            };
+            let mut worst = self.get_order_by_id(worst_id)?;
+            let is_better = match order.side {
+                OrderSide::Bid => order.price_per_share > worst.price_per_share,
+                OrderSide::Ask => order.price_per_share < worst.price_per_share,
+            };
+            if !is_better {
+                return Err(Error::OrderBookFull);
+            }
+
+            self.unlink_order(order.investment_fund_id, order.side, previous, worst_id);
+            self.release_order(&mut worst)
+        }
+
+        // Cancels an order already taken off the book and hands its escrow back to the owner.
+        fn release_order(&mut self, order: &mut Order) -> Result<()> {
+            order.is_cancelled = true;
+            self.orders.insert(order.order_id, order);
+
+            match order.side {
+                OrderSide::Bid => {
+                    let collateral = self.get_fund_by_id(order.investment_fund_id)?.collateral;
+                    self.pay_collateral(
+                        collateral,
+                        order.owner,
+                        order.remaining_share as Balance * order.price_per_share,
+                    )?;
+                }
+                OrderSide::Ask => {
+                    let owner_share = self
+                        .fund_owner_to_shares
+                        .get((order.investment_fund_id, order.owner))
+                        .unwrap_or_default();
+                    self.set_share(
+                        order.investment_fund_id,
+                        order.owner,
+                        owner_share + order.remaining_share,
+                    );
+                }
+            }
+
+            self.env().emit_event(OrderCancelled {
+                fund_id: order.investment_fund_id,
+                owner: order.owner,
+                order_id: order.order_id,
+                remaining_share: order.remaining_share,
+            });
+
+            Ok(())
+        }
+
+        // Walks the opposite side of the book in priority order and fills the taker at each
+        // maker's price until it no longer crosses. A bidder getting a better price than its
+        // limit is refunded the difference.
+        fn match_order(&mut self, fund: &mut InvestmentFund, taker: &mut Order) -> Result<()> {
+            let maker_side = match taker.side {
+                OrderSide::Bid => OrderSide::Ask,
+                OrderSide::Ask => OrderSide::Bid,
+            };
+            let mut previous = None;
+
+            for order_id in self.get_book_side(taker.investment_fund_id, maker_side) {
+                let mut maker = self.get_order_by_id(order_id)?;
+                let is_crossing = match taker.side {
+                    OrderSide::Bid => maker.price_per_share <= taker.price_per_share,
+                    OrderSide::Ask => maker.price_per_share >= taker.price_per_share,
+                };
+                if taker.remaining_share == 0 || !is_crossing {
+                    break;
+                }
+                if maker.owner == taker.owner {
+                    previous = Some(order_id);
+                    continue;
+                }
+
+                let fill = maker.remaining_share.min(taker.remaining_share);
+                maker.remaining_share -= fill;
+                taker.remaining_share -= fill;
+                let (seller, seller_escrow, buyer) = match taker.side {
+                    OrderSide::Bid => (maker.owner, maker.remaining_share, taker.owner),
+                    OrderSide::Ask => (taker.owner, taker.remaining_share, maker.owner),
+                };
+
+                let seller_share = self
+                    .fund_owner_to_shares
+                    .get((fund.investment_fund_id, seller))
+                    .unwrap_or_default();
+                let buyer_share = self
+                    .fund_owner_to_shares
+                    .get((fund.investment_fund_id, buyer))
+                    .unwrap_or_default();
+                self.update_trader(
+                    fund,
+                    seller,
+                    seller_share + seller_escrow,
+                    buyer,
+                    buyer_share + fill,
+                )?;
+                self.set_share(fund.investment_fund_id, buyer, buyer_share + fill);
+                self.orders.insert(order_id, &maker);
+                if maker.remaining_share == 0 {
+                    self.unlink_order(taker.investment_fund_id, maker_side, previous, order_id);
+                } else {
+                    previous = Some(order_id);
+                }
+
+                self.pay_collateral(
+                    fund.collateral,
+                    seller,
+                    fill as Balance * maker.price_per_share,
+                )?;
+                if taker.price_per_share > maker.price_per_share && taker.side == OrderSide::Bid {
+                    self.refund_collateral(
+                        fund.collateral,
+                        taker.owner,
+                        fill as Balance * (taker.price_per_share - maker.price_per_share),
+                    )?;
+                }
+
+                self.env().emit_event(SharesTransferred {
+                    fund_id: fund.investment_fund_id,
+                    from: seller,
+                    to: buyer,
+                    amount: fill,
+                });
+                self.env().emit_event(OrderMatched {
+                    fund_id: fund.investment_fund_id,
+                    taker: taker.owner,
+                    maker_order_id: order_id,
+                    taker_order_id: taker.order_id,
+                    share: fill,
+                    price_per_share: maker.price_per_share,
+                });
+            }
+
+            Ok(())
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        fn get_governance_by_id(&self, proposal_id: GovernanceId) -> Result<GovernanceProposal> {
+            let proposal = self.governance_proposals.get(proposal_id);
+            if proposal.is_none() {
+                return Err(Error::GovernanceNotFound);
+            }
+            Ok(proposal.unwrap())
+        }
+
+        fn validate_fees(&self, fees: &FundFees) -> Result<()> {
+            if fees.management_fee > self.max_management_fee
+                || fees.performance_fee > self.max_performance_fee
+            {
+                return Err(Error::InvalidFee);
+            }
+            Ok(())
+        }
+
+        fn execute_action(&mut self, proposal: &mut GovernanceProposal) -> Result<()> {
+            let fund_id = proposal.investment_fund_id;
+            let mut fund = self.get_fund_by_id(fund_id)?;
+
+            match proposal.action {
+                GovernanceAction::ReplaceTrader(new_trader) => {
+                    let previous_trader = fund.trader;
+                    fund.trader = new_trader;
+                    if new_trader != previous_trader {
+                        self.env().emit_event(TraderChanged {
+                            fund_id,
+                            new_trader,
+                            previous_trader,
+                        });
+                    }
+                }
+                GovernanceAction::ChangeFee(fees) => {
+                    // the admin may have lowered the limits since the vote opened
+                    if self.validate_fees(&fees).is_err() {
+                        proposal.status = GovernanceStatus::Rejected;
+                        self.env().emit_event(GovernanceExecuted {
+                            fund_id,
+                            proposal_id: proposal.proposal_id,
+                            status: GovernanceStatus::Rejected,
+                        });
+                        return Ok(());
+                    }
+                    self.accrue_fees(&mut fund, false)?;
+                    fund.fees = fees;
+                }
+                // a dissolved fund stops trading and taking money; holders leave through redeem
+                GovernanceAction::Dissolve => {
+                    fund.is_dissolved = true;
+                    fund.subscription.is_open = false;
+                }
+            }
+            self.investment_funds.insert(fund_id, &fund);
+
+            proposal.status = GovernanceStatus::Executed;
+            self.env().emit_event(GovernanceExecuted {
+                fund_id,
+                proposal_id: proposal.proposal_id,
+                status: GovernanceStatus::Executed,
+            });
+
+            Ok(())
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        // Every balance write goes through here so the history and the dividends owed on the
+        // old balance stay in step with the mapping.
+        fn set_share(&mut self, fund_id: InvestmentFundId, owner: AccountId, share: Share) {
+            if let Some(fund) = self.investment_funds.get(fund_id) {
+                let account = self.settle_dividends(&fund, owner);
+                self.fund_owner_to_dividends
+                    .insert((fund_id, owner), &account);
+            }
+
+            let count = self
+                .fund_owner_to_checkpoint_count
+                .get((fund_id, owner))
+                .unwrap_or_default();
+            let last = count
+                .checked_sub(1)
+                .and_then(|index| self.fund_owner_to_checkpoints.get((fund_id, owner, index)));
+            let (index, count) = self.next_checkpoint(count, last);
+
+            let previous_share = self
+                .fund_owner_to_shares
+                .get((fund_id, owner))
+                .unwrap_or_default();
+            let circulating_share = self
+                .fund_to_circulating_share
+                .get(fund_id)
+                .unwrap_or_default();
+            self.fund_to_circulating_share
+                .insert(fund_id, &(circulating_share - previous_share + share));
+
+            self.fund_owner_to_shares.insert((fund_id, owner), &share);
+            self.fund_owner_to_checkpoints.insert(
+                (fund_id, owner, index),
+                &Checkpoint {
+                    block: self.env().block_number(),
+                    share,
+                },
+            );
+            self.fund_owner_to_checkpoint_count
+                .insert((fund_id, owner), &count);
+        }
+
+        fn checkpoint_total_share(&mut self, fund_id: InvestmentFundId, total_share: Share) {
+            let count = self
+                .fund_to_total_checkpoint_count
+                .get(fund_id)
+                .unwrap_or_default();
+            let last = count
+                .checked_sub(1)
+                .and_then(|index| self.fund_to_total_checkpoints.get((fund_id, index)));
+            let (index, count) = self.next_checkpoint(count, last);
+
+            self.fund_to_total_checkpoints.insert(
+                (fund_id, index),
+                &Checkpoint {
+                    block: self.env().block_number(),
+                    share: total_share,
+                },
+            );
+            self.fund_to_total_checkpoint_count.insert(fund_id, &count);
+        }
+
+        // Index to write the current block's checkpoint at and the count after it; several
+        // writes within one block collapse into the last one.
+        fn next_checkpoint(&self, count: u32, last: Option<Checkpoint>) -> (u32, u32) {
+            match last {
+                Some(last) if last.block == self.env().block_number() => (count - 1, count),
+                _ => (count, count + 1),
+            }
+        }
+
+        fn get_checkpointed_share(
+            &self,
+            fund_id: InvestmentFundId,
+            owner: AccountId,
+            block: BlockNumber,
+        ) -> Share {
+            let count = self
+                .fund_owner_to_checkpoint_count
+                .get((fund_id, owner))
+                .unwrap_or_default();
+            Self::find_checkpoint(count, block, |index| {
+                self.fund_owner_to_checkpoints.get((fund_id, owner, index))
+            })
+        }
+
+        fn get_checkpointed_total_share(
+            &self,
+            fund_id: InvestmentFundId,
+            block: BlockNumber,
+        ) -> Share {
+            let count = self
+                .fund_to_total_checkpoint_count
+                .get(fund_id)
+                .unwrap_or_default();
+            Self::find_checkpoint(count, block, |index| {
+                self.fund_to_total_checkpoints.get((fund_id, index))
+            })
+        }
+
+        // Balance as of the end of `block`, binary searched over the `count` checkpoints so a
+        // lookup reads O(log n) cells.
+        fn find_checkpoint(
+            count: u32,
+            block: BlockNumber,
+            checkpoint_at: impl Fn(u32) -> Option<Checkpoint>,
+        ) -> Share {
+            let (mut low, mut high) = (0, count);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                match checkpoint_at(mid) {
+                    Some(checkpoint) if checkpoint.block <= block => low = mid + 1,
+                    _ => high = mid,
+                }
+            }
+            if low == 0 {
+                return 0;
+            }
+            checkpoint_at(low - 1)
+                .map(|checkpoint| checkpoint.share)
+                .unwrap_or_default()
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        // Moves `prize` out of the fund to its holders when the fund distributes. Shares held in
+        // escrow by proposals, auctions and asks are not in anyone's balance and earn nothing, so
+        // the prize is split over the circulating shares only.
+        fn distribute_dividends(&self, fund: &mut InvestmentFund, prize: Balance) -> Balance {
+            if fund.distribution != DistributionPolicy::Distribute {
+                return 0;
+            }
+            let total_share = self
+                .fund_to_circulating_share
+                .get(fund.investment_fund_id)
+                .unwrap_or_default() as Balance;
+            let per_share = math::mul_div(prize, math::ONE, total_share).unwrap_or_default();
+            if per_share == 0 {
+                return 0;
+            }
+
+            fund.dividend_per_share += per_share;
+            // whatever the rounding leaves behind is reinvested
+            math::mul_div(per_share, total_share, math::ONE).unwrap_or_default()
+        }
+
+        // Brings the owner's pending dividends up to the fund's accumulator using the balance
+        // they held since the last settlement.
+        fn settle_dividends(&self, fund: &InvestmentFund, owner: AccountId) -> DividendAccount {
+            let fund_id = fund.investment_fund_id;
+            let mut account = self
+                .fund_owner_to_dividends
+                .get((fund_id, owner))
+                .unwrap_or_default();
+            let share = self
+                .fund_owner_to_shares
+                .get((fund_id, owner))
+                .unwrap_or_default();
+
+            let earned = math::mul_div(
+                fund.dividend_per_share - account.paid_per_share,
+                share as Balance,
+                math::ONE,
+            )
+            .unwrap_or_default();
+            account.pending += earned;
+            account.paid_per_share = fund.dividend_per_share;
+
+            account
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        // Charges the management fee for the time since the last accrual and, when a market has
+        // just paid the fund, the performance fee on the NAV per share above the high-water mark.
+        // The caller saves the fund.
+        fn accrue_fees(
+            &mut self,
+            fund: &mut InvestmentFund,
+            with_performance: bool,
+        ) -> Result<Balance> {
+            let now = self.env().block_timestamp();
+            let elapsed = now.saturating_sub(fund.fees_accrued_at);
+            fund.fees_accrued_at = now;
+
+            if fund.total_share == 0 || (fund.fees.management_fee == 0 && !with_performance) {
+                return Ok(0);
+            }
+            let total_share = fund.total_share as Balance;
+            let net_asset_value = self.get_fund_valuation(fund)?.net_asset_value;
+
+            let mut fee = math::mul_div(
+                net_asset_value,
+                fund.fees.management_fee as Balance * elapsed as Balance,
+                BASIS_POINTS as Balance * YEAR as Balance,
+            )
+            .ok_or(Error::MathOverflow)?
+            .min(net_asset_value);
+
+            if with_performance {
+                let nav_per_share = (net_asset_value - fee) / total_share;
+                if nav_per_share > fund.high_water_mark {
+                    let gain = (nav_per_share - fund.high_water_mark)
+                        .checked_mul(total_share)
+                        .ok_or(Error::MathOverflow)?;
+                    fee = math::mul_div(
+                        gain,
+                        fund.fees.performance_fee as Balance,
+                        BASIS_POINTS as Balance,
+                    )
+                    .and_then(|performance_fee| fee.checked_add(performance_fee))
+                    .ok_or(Error::MathOverflow)?;
+                    fund.high_water_mark = (net_asset_value - fee) / total_share;
+                }
+            }
+            if fee == 0 {
+                return Ok(0);
+            }
+
+            let mut shares = 0;
+            let amount = match fund.fees.payout {
+                // new shares worth the fee at the current NAV dilute the other holders
+                FeePayout::MintShares => {
+                    if net_asset_value > fee {
+                        shares = math::mul_div(fee, total_share, net_asset_value - fee)
+                            .and_then(|shares| Share::try_from(shares).ok())
+                            .ok_or(Error::MathOverflow)?;
+                    }
+                    if shares > 0 {
+                        let trader_share = self
+                            .fund_owner_to_shares
+                            .get((fund.investment_fund_id, fund.trader))
+                            .unwrap_or_default();
+                        fund.total_share += shares;
+                        self.set_share(fund.investment_fund_id, fund.trader, trader_share + shares);
+                        self.checkpoint_total_share(fund.investment_fund_id, fund.total_share);
+                    }
+                    fee
+                }
+                // only liquid money can be paid out
+                FeePayout::PayTrader => {
+                    let amount = fee.min(fund.total_fund);
+                    fund.total_fund -= amount;
+                    self.pay_collateral(fund.collateral, fund.trader, amount)?;
+                    amount
+                }
+            };
+
+            self.env().emit_event(FeesCollected {
+                fund_id: fund.investment_fund_id,
+                trader: fund.trader,
+                amount,
+                shares,
+            });
+
+            Ok(amount)
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        fn get_outcome_allowance(
+            &self,
+            owner: AccountId,
+            operator: AccountId,
+            id: Option<OutComeId>,
+        ) -> Balance {
+            if self
+                .outcome_allowances
+                .contains((owner, operator, None::<OutComeId>))
+            {
+                return Balance::MAX;
+            }
+            self.outcome_allowances
+                .get((owner, operator, id))
+                .unwrap_or_default()
+        }
+
+        fn spend_outcome_allowance(
+            &mut self,
+            owner: AccountId,
+            id: OutComeId,
+            value: Balance,
+        ) -> core::result::Result<(), PSP37Error> {
+            let operator = self.env().caller();
+            if operator == owner {
+                return Ok(());
+            }
+
+            let allowance = self.get_outcome_allowance(owner, operator, Some(id));
+            if allowance < value {
+                return Err(PSP37Error::NotAllowed);
+            }
+            if allowance != Balance::MAX {
+                self.outcome_allowances
+                    .insert((owner, operator, Some(id)), &(allowance - value));
+            }
+
+            Ok(())
+        }
+
+        // Moves supply between holders together with its share of the cost basis, which
+        // invalid markets refund.
+        fn transfer_outcome(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            id: OutComeId,
+            value: Balance,
+        ) -> core::result::Result<(), PSP37Error> {
+            if to == AccountId::from([0u8; 32]) {
+                return Err(PSP37Error::TransferToZeroAddress);
+            }
+            let supplies = Supply::try_from(value).map_err(|_| PSP37Error::InsufficientBalance)?;
+            let _ = self.get_outcome_by_id(id)?;
+            let balance = self
+                .outcome_owner_to_claims
+                .get((id, from))
+                .unwrap_or_default()
+                .supply;
+            if balance < supplies {
+                return Err(PSP37Error::InsufficientBalance);
+            }
+
+            if from != to && supplies > 0 {
+                let cost = self.decrease_claim(id, from, supplies)?;
+                self.increase_claim(id, to, supplies, cost);
+            }
+
+            self.env().emit_event(OutcomeTransferred {
+                from: Some(from),
+                to: Some(to),
+                id,
+                value,
+            });
+
+            Ok(())
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        // The one path shares take between holders, so the trader keeps 51% or hands the fund
+        // over whichever interface moved them.
+        fn move_shares(
+            &mut self,
+            fund_id: InvestmentFundId,
+            from: AccountId,
+            to: AccountId,
+            amount: Share,
+        ) -> Result<Share> {
+            let share_of_sender = self.fund_owner_to_shares.get((fund_id, from)).unwrap_or(0);
+            let share_of_recipient = self.fund_owner_to_shares.get((fund_id, to)).unwrap_or(0);
+            let mut fund = self.get_fund_by_id(fund_id)?;
+            let previous_trader = fund.trader;
+
+            if amount > share_of_sender {
+                return Err(Error::NotEnoughShare);
+            }
+            if from == to {
+                return Ok(share_of_recipient);
+            }
+            self.update_trader(
+                &mut fund,
+                from,
+                share_of_sender - amount,
+                to,
+                share_of_recipient + amount,
+            )?;
+
+            self.set_share(fund_id, from, share_of_sender - amount);
+            self.set_share(fund_id, to, share_of_recipient + amount);
+            self.investment_funds.insert(fund_id, &fund);
+
+            self.env().emit_event(SharesTransferred {
+                fund_id,
+                from,
+                to,
+                amount,
+            });
+            if fund.trader != previous_trader {
+                self.env().emit_event(TraderChanged {
+                    fund_id,
+                    new_trader: fund.trader,
+                    previous_trader,
+                });
+            }
+
+            Ok(share_of_recipient + amount)
+        }
+
+        fn set_share_allowance(
+            &mut self,
+            fund_id: InvestmentFundId,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+        ) {
+            if value == 0 {
+                self.share_allowances.remove((fund_id, owner, spender));
+            } else {
+                self.share_allowances
+                    .insert((fund_id, owner, spender), &value);
+            }
+
+            self.env().emit_event(SharesApproved {
+                fund_id,
+                owner,
+                spender,
+                value,
+            });
+        }
+    }
+
+    #[ink(impl)]
+    impl PredictionMarket {
+        // `None` is the native token, which is always accepted.
+        fn validate_collateral(&self, collateral: Option<AccountId>) -> Result<()> {
+            match collateral {
+                Some(token) if !self.allowed_collaterals.contains(token) => {
+                    Err(Error::CollateralNotAllowed)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        // What the caller pays into a message: the attached value for native collateral, exactly
+        // `amount` out of the caller's deposited credit for a token.
+        fn receive_collateral(
+            &mut self,
+            collateral: Option<AccountId>,
+            amount: Balance,
+        ) -> Result<Balance> {
+            let caller = self.env().caller();
+            match collateral {
+                None => Ok(self.env().transferred_value()),
+                Some(token) => {
+                    if self.env().transferred_value() > 0 {
+                        return Err(Error::CollateralMismatch);
+                    }
+                    let credit = self
+                        .owner_token_to_credit
+                        .get((caller, token))
+                        .unwrap_or_default();
+                    if credit < amount {
+                        return Err(Error::DepositTooLow);
+                    }
+                    self.owner_token_to_credit
+                        .insert((caller, token), &(credit - amount));
+                    Ok(amount)
+                }
+            }
+        }
+
+        // Hands back what a message did not use; token change stays as credit.
+        fn refund_collateral(
+            &mut self,
+            collateral: Option<AccountId>,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            match collateral {
+                Some(token) if amount > 0 => {
+                    let credit = self
+                        .owner_token_to_credit
+                        .get((to, token))
+                        .unwrap_or_default();
+                    self.owner_token_to_credit
+                        .insert((to, token), &(credit + amount));
+                    Ok(())
+                }
+                _ => self.pay_collateral(collateral, to, amount),
+            }
+        }
+
+        fn pay_collateral(
+            &mut self,
+            collateral: Option<AccountId>,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            if amount == 0 {
+                return Ok(());
+            }
+            match collateral {
+                None => self
+                    .env()
+                    .transfer(to, amount)
+                    .map_err(|_| Error::TransferFailed),
+                Some(token) => {
+                    let mut psp22: contract_ref!(PSP22, MyEnvironment) = token.into();
+                    psp22
+                        .call_mut()
+                        .transfer(to, amount, Vec::new())
+                        .try_invoke()
+                        .map_err(|_| Error::TransferFailed)?
+                        .map_err(|_| Error::TransferFailed)?
+                        .map_err(|_| Error::TransferFailed)
+                }
+            }
+        }
     }
 
-    impl super::FundCore for PredictionMarket {
-        #[ink(message, payable)]
-        fn create_fund(
-            &mut self,
-            total_share: Share,
-            metadata: FundMetadata,
-        ) -> Result<InvestmentFundId> {
-            let trader = self.env().caller();
-            let total_fund = self.env().transferred_value();
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+
+        type Emitted = <PredictionMarket as ink::reflect::ContractEventBase>::Type;
+
+        const RESOLVE_DATE: Timestamp = 1_000_000;
+
+        fn accounts() -> test::DefaultAccounts<MyEnvironment> {
+            test::default_accounts::<MyEnvironment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            test::set_caller::<MyEnvironment>(caller);
+        }
+
+        fn set_value(value: Balance) {
+            test::set_value_transferred::<MyEnvironment>(value);
+        }
+
+        fn set_now(timestamp: Timestamp) {
+            test::set_block_timestamp::<MyEnvironment>(timestamp);
+        }
+
+        fn emitted() -> Vec<Emitted> {
+            test::recorded_events()
+                .map(|event| <Emitted as scale::Decode>::decode(&mut &event.data[..]).unwrap())
+                .collect()
+        }
+
+        // Deployed by alice, who is also the admin and the arbitrator.
+        fn new_contract() -> PredictionMarket {
+            set_caller(accounts().alice);
+            test::set_account_balance::<MyEnvironment>(
+                test::callee::<MyEnvironment>(),
+                1_000_000_000_000_000_000,
+            );
+            PredictionMarket::default()
+        }
+
+        fn outcome(description: &str) -> OutComePayload {
+            OutComePayload {
+                description: String::from(description),
+                deposit_per_supply: 1_000,
+                total_supply: 1_000,
+            }
+        }
+
+        fn create_event(contract: &mut PredictionMarket, pricing: PricingMode) -> EventId {
+            set_value(MIN_EVENT_DEPOSIT);
+            let event_id = contract
+                .create_event(
+                    String::from("Will it rain?"),
+                    RESOLVE_DATE,
+                    vec![outcome("Yes"), outcome("No")],
+                    EventMetadata {
+                        name: None,
+                        image_url: None,
+                        description: None,
+                    },
+                    MarketTerms {
+                        pricing,
+                        collateral: None,
+                        deposit: 0,
+                    },
+                    None,
+                )
+                .unwrap();
+            set_value(0);
+            event_id
+        }
+
+        fn create_committee_event(
+            contract: &mut PredictionMarket,
+            resolve_date: Timestamp,
+            members: Vec<AccountId>,
+            threshold: u32,
+        ) -> Result<EventId> {
+            set_value(MIN_EVENT_DEPOSIT);
+            let event_id = contract.create_event(
+                String::from("Will it rain?"),
+                resolve_date,
+                vec![outcome("Yes"), outcome("No")],
+                EventMetadata {
+                    name: None,
+                    image_url: None,
+                    description: None,
+                },
+                MarketTerms {
+                    pricing: PricingMode::Fixed,
+                    collateral: None,
+                    deposit: 0,
+                },
+                Some(ResolverPayload::Committee(Committee { members, threshold })),
+            );
+            set_value(0);
+            event_id
+        }
+
+        // LONG is the first outcome and pays in full at 110, SHORT the second pays in full at 10.
+        fn create_scalar_event(contract: &mut PredictionMarket) -> EventId {
+            set_value(MIN_EVENT_DEPOSIT);
+            let event_id = contract
+                .create_event(
+                    String::from("How many millimetres of rain?"),
+                    RESOLVE_DATE,
+                    vec![outcome("Long"), outcome("Short")],
+                    EventMetadata {
+                        name: None,
+                        image_url: None,
+                        description: None,
+                    },
+                    MarketTerms {
+                        pricing: PricingMode::Fixed,
+                        collateral: None,
+                        deposit: 0,
+                    },
+                    Some(ResolverPayload::Scalar(OracleBindingPayload {
+                        oracle: accounts().frank,
+                        query: b"rain".to_vec(),
+                        answers: vec![(110, 0), (10, 1)],
+                    })),
+                )
+                .unwrap();
+            set_value(0);
+            event_id
+        }
+
+        fn create_fund(contract: &mut PredictionMarket, total_share: Share) -> InvestmentFundId {
+            set_value(MIN_FUND_DEPOSIT);
+            let fund_id = contract
+                .create_fund(
+                    total_share,
+                    FundMetadata {
+                        name: None,
+                        image_url: None,
+                    },
+                    None,
+                    0,
+                )
+                .unwrap();
+            set_value(0);
+            fund_id
+        }
+
+        fn bet(
+            contract: &mut PredictionMarket,
+            outcome_id: OutComeId,
+            fund_id: InvestmentFundId,
+            supplies: Supply,
+        ) {
+            let cost = contract.quote_bet(outcome_id, supplies).unwrap().cost;
+            set_value(cost);
+            contract.bet(outcome_id, fund_id, supplies).unwrap();
+            set_value(0);
+        }
+
+        // An uncontested optimistic resolution proposed by eve once the event is due.
+        fn propose_and_finalize(
+            contract: &mut PredictionMarket,
+            event_id: EventId,
+            outcome_id: OutComeId,
+        ) {
+            set_caller(accounts().eve);
+            set_now(RESOLVE_DATE);
+            set_value(MIN_RESOLUTION_BOND);
+            contract.propose_resolution(event_id, outcome_id).unwrap();
+            set_value(0);
+            set_now(RESOLVE_DATE + DEFAULT_DISPUTE_WINDOW);
+            contract.finalize_resolution(event_id).unwrap();
+        }
+
+        #[ink::test]
+        fn create_event_emits_event_created() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+
+            match emitted().last() {
+                Some(Emitted::EventCreated(event)) => {
+                    assert_eq!(event.event_id, event_id);
+                    assert_eq!(event.owner, accounts().alice);
+                    assert_eq!(event.outcomes, vec![0, 1]);
+                    assert_eq!(event.resolve_date, RESOLVE_DATE);
+                    assert_eq!(event.pool, MIN_EVENT_DEPOSIT);
+                }
+                _ => panic!("expected EventCreated"),
+            }
+        }
+
+        #[ink::test]
+        fn create_fund_emits_fund_created() {
+            let mut contract = new_contract();
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            match emitted().last() {
+                Some(Emitted::FundCreated(event)) => {
+                    assert_eq!(event.fund_id, fund_id);
+                    assert_eq!(event.trader, accounts().bob);
+                    assert_eq!(event.total_share, 100);
+                    assert_eq!(event.total_fund, MIN_FUND_DEPOSIT);
+                }
+                _ => panic!("expected FundCreated"),
+            }
+        }
+
+        #[ink::test]
+        fn transfer_share_emits_shares_transferred_and_trader_changed() {
+            let mut contract = new_contract();
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            contract
+                .transfer_share(fund_id, accounts().charlie, 60)
+                .unwrap();
+
+            let events = emitted();
+            match &events[events.len() - 2] {
+                Emitted::SharesTransferred(event) => {
+                    assert_eq!(event.fund_id, fund_id);
+                    assert_eq!(event.from, accounts().bob);
+                    assert_eq!(event.to, accounts().charlie);
+                    assert_eq!(event.amount, 60);
+                }
+                _ => panic!("expected SharesTransferred"),
+            }
+            match events.last() {
+                Some(Emitted::TraderChanged(event)) => {
+                    assert_eq!(event.fund_id, fund_id);
+                    assert_eq!(event.new_trader, accounts().charlie);
+                    assert_eq!(event.previous_trader, accounts().bob);
+                }
+                _ => panic!("expected TraderChanged"),
+            }
+        }
+
+        #[ink::test]
+        fn bet_emits_bet_placed() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            bet(&mut contract, 0, fund_id, 10);
+
+            match emitted().last() {
+                Some(Emitted::BetPlaced(event)) => {
+                    assert_eq!(event.event_id, event_id);
+                    assert_eq!(event.fund_id, fund_id);
+                    assert_eq!(event.outcome_id, 0);
+                    assert_eq!(event.supplies, 10);
+                    assert_eq!(event.deposit, 10_000);
+                }
+                _ => panic!("expected BetPlaced"),
+            }
+        }
+
+        #[ink::test]
+        fn lmsr_bet_charges_the_quote_and_refunds_the_excess() {
+            let mut contract = new_contract();
+            let event_id = create_event(
+                &mut contract,
+                PricingMode::Lmsr {
+                    liquidity: 100,
+                    payout_per_supply: 1_000_000,
+                },
+            );
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            let quote = contract.quote_bet(0, 10).unwrap();
+            assert!(quote.cost > 5_000_000 && quote.cost < 10_000_000);
+            let probabilities: Probability = quote.probabilities.iter().map(|p| p.1).sum();
+            assert!(probabilities.abs_diff(PROBABILITY_ONE) <= 2);
+
+            let balance = test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap();
+            set_value(quote.cost + 5_000);
+            contract.bet(0, fund_id, 10).unwrap();
+            set_value(0);
+
+            assert_eq!(
+                test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap(),
+                balance + 5_000
+            );
+            let fund = contract.get_fund_by_id(fund_id).unwrap();
+            assert_eq!(fund.total_fund, MIN_FUND_DEPOSIT - quote.cost);
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert_eq!(market.pool, MIN_EVENT_DEPOSIT + quote.cost);
+            assert_eq!(contract.get_position(0, fund_id).cost_basis, quote.cost);
+
+            // the next supply of the same outcome is dearer
+            assert!(contract.quote_bet(0, 10).unwrap().cost > quote.cost);
+        }
+
+        #[ink::test]
+        fn bet_below_the_quote_is_rejected() {
+            let mut contract = new_contract();
+            create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            set_value(9_999);
+            assert_eq!(contract.bet(0, fund_id, 10), Err(Error::DepositTooLow));
+        }
+
+        #[ink::test]
+        fn repeated_bets_on_one_outcome_accumulate() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            bet(&mut contract, 0, fund_id, 10);
+            bet(&mut contract, 0, fund_id, 15);
+
+            let position = contract.get_position(0, fund_id);
+            assert_eq!(position.supply, 25);
+            assert_eq!(position.cost_basis, 25_000);
+            assert_eq!(contract.outcome_to_funds.get(0), Some(vec![fund_id]));
+            assert_eq!(contract.fund_to_outcomes.get(fund_id), Some(vec![0]));
+
+            let detail = contract.get_event_detail(event_id).unwrap();
+            assert_eq!(detail.3[0].2.len(), 1);
+            assert_eq!(detail.3[0].2[0].1, 25);
+            assert_eq!(detail.3[0].1.available_supply, 975);
+        }
+
+        #[ink::test]
+        fn bets_on_different_outcomes_keep_separate_positions() {
+            let mut contract = new_contract();
+            create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            bet(&mut contract, 0, fund_id, 10);
+            bet(&mut contract, 1, fund_id, 5);
+            bet(&mut contract, 0, fund_id, 10);
+
+            assert_eq!(contract.get_position(0, fund_id).supply, 20);
+            assert_eq!(contract.get_position(1, fund_id).supply, 5);
+            assert_eq!(contract.fund_to_outcomes.get(fund_id), Some(vec![0, 1]));
+            assert_eq!(contract.outcome_to_funds.get(0), Some(vec![fund_id]));
+            assert_eq!(contract.outcome_to_funds.get(1), Some(vec![fund_id]));
+            assert_eq!(
+                contract
+                    .get_fund_outcomes(fund_id)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(outcome, supply)| (outcome.outcome_id, supply))
+                    .collect::<Vec<_>>(),
+                vec![(0, 20), (1, 5)]
+            );
+        }
+
+        #[ink::test]
+        fn resolution_pays_a_fund_that_bet_twice_once() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let winner = create_fund(&mut contract, 100);
+            set_caller(accounts().charlie);
+            let loser = create_fund(&mut contract, 100);
+
+            set_caller(accounts().bob);
+            bet(&mut contract, 0, winner, 10);
+            bet(&mut contract, 0, winner, 10);
+            set_caller(accounts().charlie);
+            bet(&mut contract, 1, loser, 30);
+
+            propose_and_finalize(&mut contract, event_id, 0);
+
+            let pool = MIN_EVENT_DEPOSIT + 50_000;
+            let prize_per_supply = pool / 20;
+            assert_eq!(
+                contract.get_fund_by_id(winner).unwrap().total_fund,
+                MIN_FUND_DEPOSIT - 20_000 + 20 * prize_per_supply
+            );
+            assert_eq!(
+                contract.get_fund_by_id(loser).unwrap().total_fund,
+                MIN_FUND_DEPOSIT - 30_000
+            );
+            assert_eq!(
+                contract.get_event_by_id(event_id).unwrap().1.pool,
+                pool - 20 * prize_per_supply
+            );
+        }
+
+        #[ink::test]
+        fn finalized_resolution_emits_market_resolved() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+
+            propose_and_finalize(&mut contract, event_id, 0);
+
+            match emitted().last() {
+                Some(Emitted::MarketResolved(event)) => {
+                    assert_eq!(event.event_id, event_id);
+                    assert_eq!(event.winning_outcome, 0);
+                    assert_eq!(event.prize_per_supply, (MIN_EVENT_DEPOSIT + 10_000) / 10);
+                }
+                _ => panic!("expected MarketResolved"),
+            }
+        }
+
+        #[ink::test]
+        fn proposals_emit_created_and_accepted() {
+            let mut contract = new_contract();
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            let trade_id = contract
+                .create_proposal(fund_id, 10, 1_000, 1, None, None)
+                .unwrap();
+            match emitted().last() {
+                Some(Emitted::ProposalCreated(event)) => {
+                    assert_eq!(event.fund_id, fund_id);
+                    assert_eq!(event.proponent, accounts().bob);
+                    assert_eq!(event.trade_id, trade_id);
+                    assert_eq!(event.share, 10);
+                    assert_eq!(event.price_per_share, 1_000);
+                    assert_eq!(event.proposed_person, None);
+                }
+                _ => panic!("expected ProposalCreated"),
+            }
+
+            set_caller(accounts().charlie);
+            set_value(4_000);
+            contract.accept_proposal(trade_id, 4).unwrap();
+            match emitted().last() {
+                Some(Emitted::ProposalAccepted(event)) => {
+                    assert_eq!(event.fund_id, fund_id);
+                    assert_eq!(event.buyer, accounts().charlie);
+                    assert_eq!(event.trade_id, trade_id);
+                    assert_eq!(event.share, 4);
+                    assert_eq!(event.price, 4_000);
+                    assert_eq!(event.remaining_share, 6);
+                }
+                _ => panic!("expected ProposalAccepted"),
+            }
+        }
+
+        #[ink::test]
+        fn bets_close_at_the_resolve_date() {
+            let mut contract = new_contract();
+            create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+
+            set_now(RESOLVE_DATE - 1);
+            bet(&mut contract, 0, fund_id, 10);
+
+            set_now(RESOLVE_DATE);
+            set_value(10_000);
+            assert_eq!(
+                contract.bet(0, fund_id, 10),
+                Err(Error::ResolveDateNotMatch)
+            );
+        }
+
+        #[ink::test]
+        fn cancelled_event_refunds_stakes_and_deposit() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+            bet(&mut contract, 1, fund_id, 5);
+
+            // only the owner cancels before the resolve date
+            assert_eq!(contract.cancel_event(event_id), Err(Error::NotOwner));
+            set_caller(accounts().alice);
+            contract.cancel_event(event_id).unwrap();
+            assert!(contract.get_event_by_id(event_id).unwrap().1.status == MarketStatus::Invalid);
+
+            // the deposit waits until every stake could be refunded
+            assert_eq!(contract.reclaim_deposit(event_id), Ok(MIN_EVENT_DEPOSIT));
+            assert_eq!(
+                contract.reclaim_deposit(event_id),
+                Err(Error::NothingToClaim)
+            );
+
+            assert_eq!(contract.claim_refund(event_id, fund_id), Ok(15_000));
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().total_fund,
+                MIN_FUND_DEPOSIT
+            );
+            assert_eq!(
+                contract.claim_refund(event_id, fund_id),
+                Err(Error::NothingToClaim)
+            );
+            assert_eq!(contract.get_event_by_id(event_id).unwrap().1.pool, 0);
+        }
+
+        #[ink::test]
+        fn owner_cannot_cancel_after_the_resolve_date() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().alice);
+            contract.set_admin(accounts().frank).unwrap();
+
+            set_now(RESOLVE_DATE);
+            assert_eq!(
+                contract.cancel_event(event_id),
+                Err(Error::ResolveDateNotMatch)
+            );
+            set_caller(accounts().frank);
+            contract.cancel_event(event_id).unwrap();
+        }
+
+        #[ink::test]
+        fn claim_refund_fails_instead_of_short_paying() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+            set_caller(accounts().alice);
+            contract.cancel_event(event_id).unwrap();
+
+            let mut market = contract.get_event_by_id(event_id).unwrap().1;
+            market.pool = 9_999;
+            contract.event_markets.insert(event_id, &market);
+
+            assert_eq!(
+                contract.claim_refund(event_id, fund_id),
+                Err(Error::NotEnoughBalance)
+            );
+        }
+
+        #[ink::test]
+        fn admin_can_be_rotated() {
+            let mut contract = new_contract();
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.set_admin(accounts().bob), Err(Error::NotOwner));
+
+            set_caller(accounts().alice);
+            contract.set_admin(accounts().bob).unwrap();
+            assert_eq!(contract.admin, Some(accounts().bob));
+            assert_eq!(
+                contract.set_collateral_allowed(accounts().eve, true),
+                Err(Error::NotOwner)
+            );
+
+            set_caller(accounts().bob);
+            contract
+                .set_collateral_allowed(accounts().eve, true)
+                .unwrap();
+        }
+
+        #[ink::test]
+        fn finalized_winner_nobody_betted_on_invalidates_the_market() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+
+            propose_and_finalize(&mut contract, event_id, 1);
+
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert!(market.status == MarketStatus::Invalid);
+            assert!(matches!(
+                emitted().last(),
+                Some(Emitted::MarketCancelled(_))
+            ));
+            assert_eq!(contract.claim_refund(event_id, fund_id), Ok(10_000));
+        }
+
+        #[ink::test]
+        fn arbitrator_can_pick_a_winner_nobody_betted_on() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+
+            set_now(RESOLVE_DATE);
+            set_caller(accounts().eve);
+            set_value(MIN_RESOLUTION_BOND);
+            contract.propose_resolution(event_id, 0).unwrap();
+            set_caller(accounts().frank);
+            contract.dispute_resolution(event_id).unwrap();
+            set_value(0);
+
+            let balance = test::get_account_balance::<MyEnvironment>(accounts().frank).unwrap();
+            set_caller(accounts().alice);
+            contract.settle_dispute(event_id, Some(1)).unwrap();
+
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert!(market.status == MarketStatus::Invalid);
+            // the disputer was right and takes both bonds
+            assert_eq!(
+                test::get_account_balance::<MyEnvironment>(accounts().frank).unwrap(),
+                balance + 2 * MIN_RESOLUTION_BOND
+            );
+            match emitted().last() {
+                Some(Emitted::DisputeSettled(event)) => {
+                    assert_eq!(event.bond_winner, accounts().frank);
+                    assert_eq!(event.winner, Some(1));
+                    assert_eq!(event.slashed_bond, MIN_RESOLUTION_BOND);
+                }
+                _ => panic!("expected DisputeSettled"),
+            }
+        }
+
+        #[ink::test]
+        fn dispute_deadline_is_fixed_at_proposal() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+
+            set_now(RESOLVE_DATE);
+            set_caller(accounts().eve);
+            set_value(MIN_RESOLUTION_BOND);
+            contract.propose_resolution(event_id, 0).unwrap();
+            set_value(0);
+            assert_eq!(
+                contract.get_resolution(event_id).unwrap().dispute_deadline,
+                RESOLVE_DATE + DEFAULT_DISPUTE_WINDOW
+            );
+
+            // a longer window set afterwards doesn't keep the proposal open
+            set_caller(accounts().alice);
+            contract
+                .set_resolution_config(
+                    accounts().alice,
+                    MIN_RESOLUTION_BOND,
+                    10 * DEFAULT_DISPUTE_WINDOW,
+                )
+                .unwrap();
+            set_now(RESOLVE_DATE + DEFAULT_DISPUTE_WINDOW);
+            set_caller(accounts().frank);
+            set_value(MIN_RESOLUTION_BOND);
+            assert_eq!(
+                contract.dispute_resolution(event_id),
+                Err(Error::DisputeWindowClosed)
+            );
+            set_value(0);
+            contract.finalize_resolution(event_id).unwrap();
+            assert!(contract.get_event_by_id(event_id).unwrap().1.status == MarketStatus::Resolved);
+        }
+
+        #[ink::test]
+        fn subscriptions_cannot_dilute_the_trader_below_51_percent() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .set_subscription(
+                    fund_id,
+                    SubscriptionConfig {
+                        is_open: true,
+                        share_cap: None,
+                        min_ticket: 0,
+                    },
+                )
+                .unwrap();
+
+            // at the fund's NAV of MIN_FUND_DEPOSIT for 1000 shares, this buys 1000 shares
+            set_caller(accounts().bob);
+            set_value(MIN_FUND_DEPOSIT);
+            assert_eq!(
+                contract.subscribe(fund_id, 0),
+                Err(Error::TraderNotIdentitied)
+            );
+
+            set_value(MIN_FUND_DEPOSIT * 9 / 10);
+            assert_eq!(contract.subscribe(fund_id, 0), Ok(900));
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().trader,
+                accounts().alice
+            );
+
+            // the trader can always add to their own stake
+            set_caller(accounts().alice);
+            set_value(MIN_FUND_DEPOSIT);
+            assert!(contract.subscribe(fund_id, 0).is_ok());
+        }
+
+        #[ink::test]
+        fn partial_fills_hand_over_control_once_the_buyer_holds_51_percent() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            assert_eq!(
+                contract.create_proposal(fund_id, 600, 1_000, 0, None, None),
+                Err(Error::NotEnoughShare)
+            );
+            let trade_id = contract
+                .create_proposal(fund_id, 600, 1_000, 100, None, None)
+                .unwrap();
+
+            set_caller(accounts().bob);
+            set_value(300 * 1_000);
+            contract.accept_proposal(trade_id, 300).unwrap();
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().trader,
+                accounts().alice
+            );
+
+            // no single fill reaches 51%, but bob's balance does after the second one
+            contract.accept_proposal(trade_id, 300).unwrap();
+            set_value(0);
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().trader,
+                accounts().bob
+            );
+
+            let fills = contract.get_trade_fills(trade_id).unwrap();
+            assert_eq!(fills.len(), 2);
+            assert!(fills
+                .iter()
+                .all(|fill| fill.buyer == accounts().bob && fill.share == 300));
+        }
+
+        #[ink::test]
+        fn order_book_matches_in_price_order() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+
+            let worse_ask = contract.place_ask(fund_id, 100, 20_000_000).unwrap();
+            contract.place_ask(fund_id, 100, 10_000_000).unwrap();
+            assert_eq!(
+                contract.place_ask(fund_id, 10, 10_000_000),
+                Err(Error::InvalidOrder)
+            );
+            let asks = contract.get_order_book(fund_id, 10).unwrap().asks;
+            assert_eq!(
+                asks.iter()
+                    .map(|level| level.price_per_share)
+                    .collect::<Vec<_>>(),
+                vec![10_000_000, 20_000_000]
+            );
+
+            // takes the cheaper ask in full, then half of the other one
+            set_caller(accounts().bob);
+            set_value(150 * 20_000_000);
+            contract.place_bid(fund_id, 150, 20_000_000).unwrap();
+            set_value(0);
+            assert_eq!(contract.get_owner_share(fund_id, accounts().bob), Ok(150));
+            let book = contract.get_order_book(fund_id, 10).unwrap();
+            assert!(book.bids.is_empty());
+            assert_eq!(book.asks.len(), 1);
+            assert_eq!(book.asks[0].share, 50);
+
+            set_caller(accounts().alice);
+            contract.cancel_order(worse_ask).unwrap();
+            assert!(contract
+                .get_order_book(fund_id, 10)
+                .unwrap()
+                .asks
+                .is_empty());
+            assert_eq!(contract.get_owner_share(fund_id, accounts().alice), Ok(850));
+        }
+
+        #[ink::test]
+        fn full_order_book_sides_evict_their_worst_order() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+
+            set_caller(accounts().bob);
+            set_value(MIN_ORDER_VALUE);
+            let mut worst = 0;
+            for _ in 0..MAX_BOOK_ORDERS {
+                worst = contract.place_bid(fund_id, 1, MIN_ORDER_VALUE).unwrap();
+            }
+            // an order no better than the worst resting one can't get in
+            assert_eq!(
+                contract.place_bid(fund_id, 1, MIN_ORDER_VALUE),
+                Err(Error::OrderBookFull)
+            );
+            set_value(0);
+
+            // a better one takes the place of the last order at the worst price
+            let balance = test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap();
+            set_caller(accounts().charlie);
+            set_value(2 * MIN_ORDER_VALUE);
+            contract.place_bid(fund_id, 1, 2 * MIN_ORDER_VALUE).unwrap();
+            set_value(0);
+            assert!(contract.get_order_by_id(worst).unwrap().is_cancelled);
+            assert_eq!(
+                test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap(),
+                balance + MIN_ORDER_VALUE
+            );
+            let bids = contract.get_order_book(fund_id, 2).unwrap().bids;
+            assert_eq!(bids[0].price_per_share, 2 * MIN_ORDER_VALUE);
+            assert_eq!(bids[1].orders, MAX_BOOK_ORDERS - 1);
+
+            // a crossing order that doesn't rest still goes through
+            set_caller(accounts().alice);
+            contract.place_ask(fund_id, 1, MIN_ORDER_VALUE).unwrap();
+            assert_eq!(
+                contract.get_order_book(fund_id, 1).unwrap().bids[0].orders,
+                MAX_BOOK_ORDERS - 1
+            );
+        }
+
+        fn english_auction() -> AuctionMode {
+            AuctionMode::English {
+                min_increment: 1,
+                extension: 0,
+            }
+        }
+
+        #[ink::test]
+        fn trader_cannot_auction_a_block_that_loses_control() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
+
+            // neither the trader's 40% left nor the 20% sold would reach 51%
+            assert_eq!(
+                contract.create_auction(fund_id, 200, 10_000_000, None, english_auction()),
+                Err(Error::TraderNotIdentitied)
+            );
+            assert!(contract
+                .create_auction(fund_id, 600, 10_000_000, None, english_auction())
+                .is_ok());
+        }
+
+        #[ink::test]
+        fn auction_that_cannot_settle_refunds_the_bidder() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            let trade_id = contract
+                .create_auction(fund_id, 300, 10_000_000, None, english_auction())
+                .unwrap();
+
+            let price = 300 * 10_000_000;
+            set_caller(accounts().bob);
+            set_value(price);
+            contract.bid_auction(trade_id, 10_000_000).unwrap();
+            set_value(0);
+
+            // escrowing another 30% leaves the trader under 51% outside the auction
+            set_caller(accounts().alice);
+            contract
+                .create_proposal(fund_id, 300, 1_000, 1, None, None)
+                .unwrap();
+
+            let bob_balance = test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap();
+            set_now(DEFAULT_DURATION + 1);
+            contract.settle_auction(trade_id).unwrap();
+
+            assert_eq!(
+                test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap(),
+                bob_balance + price
+            );
+            assert_eq!(contract.get_owner_share(fund_id, accounts().alice), Ok(700));
+            assert_eq!(contract.get_owner_share(fund_id, accounts().bob), Ok(0));
+            assert!(contract.get_trade_by_id(trade_id).unwrap().is_cancelled);
+        }
+
+        #[ink::test]
+        fn auction_bid_increment_overflow_is_rejected() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            let auction = AuctionMode::English {
+                min_increment: Balance::MAX,
+                extension: 0,
+            };
+            let trade_id = contract
+                .create_auction(fund_id, 600, 10_000_000, None, auction)
+                .unwrap();
+
+            set_caller(accounts().bob);
+            set_value(600 * 10_000_000);
+            contract.bid_auction(trade_id, 10_000_000).unwrap();
+            set_caller(accounts().charlie);
+            assert_eq!(
+                contract.bid_auction(trade_id, 20_000_000),
+                Err(Error::MathOverflow)
+            );
+        }
+
+        #[ink::test]
+        fn governance_needs_a_block_before_the_snapshot() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+
+            // at block 0 the snapshot would be the current block itself
+            assert_eq!(
+                contract.propose_governance(fund_id, GovernanceAction::Dissolve),
+                Err(Error::NotShareholder)
+            );
+            test::advance_block::<MyEnvironment>();
+            assert!(contract
+                .propose_governance(fund_id, GovernanceAction::Dissolve)
+                .is_ok());
+        }
+
+        #[ink::test]
+        fn governance_votes_with_shares_held_at_the_snapshot() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
+            test::advance_block::<MyEnvironment>();
+
+            set_caller(accounts().bob);
+            let proposal_id = contract
+                .propose_governance(fund_id, GovernanceAction::ReplaceTrader(accounts().bob))
+                .unwrap();
+
+            // shares moved after the snapshot neither add nor remove votes
+            contract
+                .transfer_share(fund_id, accounts().charlie, 300)
+                .unwrap();
+            set_caller(accounts().charlie);
+            assert_eq!(
+                contract.vote_governance(proposal_id, true),
+                Err(Error::NotShareholder)
+            );
+            set_caller(accounts().bob);
+            contract.vote_governance(proposal_id, true).unwrap();
+            assert_eq!(
+                contract.vote_governance(proposal_id, true),
+                Err(Error::AlreadyVoted)
+            );
+
+            set_caller(accounts().alice);
+            contract.vote_governance(proposal_id, false).unwrap();
+            assert!(contract.execute_governance(proposal_id) == Err(Error::VotingOpen));
+            set_now(2 * GOVERNANCE_VOTING_PERIOD);
+            assert!(contract.execute_governance(proposal_id) == Ok(GovernanceStatus::Rejected));
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().trader,
+                accounts().alice
+            );
+        }
+
+        #[ink::test]
+        fn governance_majority_executes_immediately() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
+            test::advance_block::<MyEnvironment>();
 
-            if total_share < 100 {
-                return Err(Error::AtLeast100Share);
-            }
+            set_caller(accounts().bob);
+            let proposal_id = contract
+                .propose_governance(fund_id, GovernanceAction::ReplaceTrader(accounts().bob))
+                .unwrap();
+            contract.vote_governance(proposal_id, true).unwrap();
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().trader,
+                accounts().alice
+            );
 
-            if total_share < MIN_FUND_SHARE {
-                return Err(Error::NotDivisibleBy100);
+            set_caller(accounts().alice);
+            contract.vote_governance(proposal_id, true).unwrap();
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().trader,
+                accounts().bob
+            );
+            assert_eq!(
+                contract.vote_governance(proposal_id, false),
+                Err(Error::VotingClosed)
+            );
+        }
+
+        #[ink::test]
+        fn share_history_is_searchable_by_block() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+
+            // bob receives 10 shares in two transfers in each of blocks 1..=20
+            for _ in 1..=20u32 {
+                test::advance_block::<MyEnvironment>();
+                contract.transfer_share(fund_id, accounts().bob, 5).unwrap();
+                contract.transfer_share(fund_id, accounts().bob, 5).unwrap();
             }
 
-            if total_fund < MIN_FUND_DEPOSIT {
-                return Err(Error::DepositTooLow);
+            for block in 0..=25u32 {
+                let expected = 10 * block.min(20) as Share;
+                assert_eq!(
+                    contract.get_share_at(fund_id, accounts().bob, block),
+                    Ok(expected)
+                );
+                assert_eq!(
+                    contract.get_share_at(fund_id, accounts().alice, block),
+                    Ok(1_000 - expected)
+                );
+                assert_eq!(contract.get_total_share_at(fund_id, block), Ok(1_000));
             }
+            assert_eq!(
+                contract
+                    .fund_owner_to_checkpoint_count
+                    .get((fund_id, accounts().bob)),
+                Some(20)
+            );
+        }
 
-            let new_fund = self.new_fund(trader, total_share, total_fund, metadata);
-            self.investment_funds
-                .insert(new_fund.investment_fund_id, &new_fund);
-            self.fund_owner_to_shares
-                .insert((new_fund.investment_fund_id, trader), &(total_share));
+        #[ink::test]
+        fn escrowed_shares_do_not_dilute_dividends() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .set_distribution_policy(fund_id, DistributionPolicy::Distribute)
+                .unwrap();
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
+            // 300 of alice's 600 shares sit in escrow
+            contract
+                .create_proposal(fund_id, 300, 1_000, 1, None, None)
+                .unwrap();
+            bet(&mut contract, 0, fund_id, 10);
 
-            Ok(new_fund.investment_fund_id)
+            propose_and_finalize(&mut contract, event_id, 0);
+
+            let distributed = emitted()
+                .into_iter()
+                .find_map(|event| match event {
+                    Emitted::DividendsDistributed(event) => Some(event.amount),
+                    _ => None,
+                })
+                .unwrap();
+            let alice = contract.get_dividends(fund_id, accounts().alice).unwrap();
+            let bob = contract.get_dividends(fund_id, accounts().bob).unwrap();
+            assert!(distributed > 0);
+            assert!(alice + bob <= distributed && distributed - (alice + bob) <= 1);
+            assert_eq!(alice * 4 / 3, bob);
         }
 
-        #[ink(message)]
-        fn transfer_share(
-            &mut self,
-            fund_id: InvestmentFundId,
-            recipient: AccountId,
-            amount: Share,
-        ) -> Result<(AccountId, Share)> {
-            let sender = self.env().caller();
-            let share_of_sender = self
-                .fund_owner_to_shares
-                .get((fund_id, sender))
-                .unwrap_or(0);
-            let share_of_recipient = self
-                .fund_owner_to_shares
-                .get((fund_id, recipient))
-                .unwrap_or(0);
-            let mut fund = self.get_fund_by_id(fund_id)?;
+        #[ink::test]
+        fn failing_fee_accrual_does_not_block_resolution() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .set_fees(
+                    fund_id,
+                    FundFees {
+                        management_fee: 100,
+                        performance_fee: 1_000,
+                        payout: FeePayout::MintShares,
+                    },
+                )
+                .unwrap();
+            bet(&mut contract, 0, fund_id, 10);
 
-            if amount > share_of_sender {
-                return Err(Error::NotEnoughShare);
-            }
-            if sender == fund.trader {
-                if ((amount as i64 * 100) / fund.total_share as i64) >= 51 {
-                    fund.trader = recipient;
-                } else if (((share_of_sender - amount) * 100 / fund.total_share) < 51)
-                    && (((share_of_recipient + amount) * 100 / fund.total_share) < 51)
-                {
-                    return Err(Error::TraderNotIdentitied);
-                }
-            }
+            // a position the fund can't value makes every fee accrual fail
+            let mut outcomes = contract.fund_to_outcomes.get(fund_id).unwrap();
+            outcomes.push(999);
+            contract.fund_to_outcomes.insert(fund_id, &outcomes);
+            let fees_accrued_at = contract.get_fund_by_id(fund_id).unwrap().fees_accrued_at;
 
-            self.fund_owner_to_shares
-                .insert((fund_id, sender), &(share_of_sender - amount));
-            self.fund_owner_to_shares
-                .insert((fund_id, recipient), &(share_of_recipient + amount));
-            self.investment_funds.insert(fund_id, &fund);
-            Ok((recipient, share_of_recipient + amount))
+            propose_and_finalize(&mut contract, event_id, 0);
+
+            let fund = contract.get_fund_by_id(fund_id).unwrap();
+            assert_eq!(fund.fees_accrued_at, fees_accrued_at);
+            assert_eq!(fund.total_share, 1_000);
+            assert!(fund.total_fund > MIN_FUND_DEPOSIT);
         }
 
-        #[ink(message, payable)]
-        fn bet(
-            &mut self,
-            outcome_id: OutComeId,
-            fund_id: InvestmentFundId,
-            supplies: Supply,
-        ) -> Result<()> {
-            let deposit = self.env().transferred_value();
-            if self.get_fund_by_id(fund_id)?.trader != self.env().caller() {
-                return Err(Error::NotOwner);
-            }
+        #[ink::test]
+        fn fee_change_above_lowered_limits_is_rejected_at_execution() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
+            test::advance_block::<MyEnvironment>();
 
-            let outcome = self.outcomes.get(outcome_id).unwrap();
-            let mut market_outcomes = self.market_outcomes.get(outcome_id).unwrap();
+            let fees = FundFees {
+                management_fee: 300,
+                performance_fee: 2_000,
+                payout: FeePayout::PayTrader,
+            };
+            set_caller(accounts().bob);
+            let proposal_id = contract
+                .propose_governance(fund_id, GovernanceAction::ChangeFee(fees))
+                .unwrap();
+            contract.vote_governance(proposal_id, true).unwrap();
 
-            let mut market = self.get_event_by_id(outcome.event_id)?.1;
-            let mut fund = self.get_fund_by_id(fund_id)?;
-            if market.is_resolved || self.env().block_timestamp() < market.resolve_date {
-                return Err(Error::ResolveDateNotMatch);
-            }
-            if (market_outcomes.available_supply as i64 - supplies as i64) < 0 {
-                return Err(Error::OutOfSupply);
-            }
-            if supplies <= 0 {
-                return Err(Error::MoreThanOneSupply);
-            }
-            if deposit < (supplies as Balance * outcome.deposit_per_supply) {
-                return Err(Error::DepositTooLow);
-            }
-            if deposit > fund.total_fund {
-                return Err(Error::NotEnoughBalance);
-            }
-            market_outcomes.available_supply -= supplies;
+            set_caller(accounts().alice);
+            contract.set_fee_limits(200, 2_000).unwrap();
+            contract.vote_governance(proposal_id, true).unwrap();
 
-            let mut funds_of_outcome = self.outcome_to_funds.get(outcome_id).unwrap_or(Vec::new());
-            let mut outcomes_of_fund = self.fund_to_outcomes.get(fund_id).unwrap_or(Vec::new());
-            funds_of_outcome.push(fund_id);
-            outcomes_of_fund.push(outcome_id);
+            let proposal = contract.get_governance_by_id(proposal_id).unwrap();
+            assert!(proposal.status == GovernanceStatus::Rejected);
+            assert_eq!(
+                contract
+                    .get_fund_by_id(fund_id)
+                    .unwrap()
+                    .fees
+                    .management_fee,
+                0
+            );
+        }
 
-            fund.total_fund -= deposit;
-            market.pool += deposit;
+        #[ink::test]
+        fn outcome_tokens_cover_account_claims() {
+            let mut contract = new_contract();
+            create_event(&mut contract, PricingMode::Fixed);
+            let fund_id = create_fund(&mut contract, 1_000);
+            bet(&mut contract, 0, fund_id, 10);
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
 
-            self.market_outcomes.insert(outcome_id, &market_outcomes);
-            self.outcome_fund_to_supplies
-                .insert((outcome_id, fund_id), &supplies);
-            self.outcome_to_funds.insert(outcome_id, &funds_of_outcome);
-            self.fund_to_outcomes.insert(fund_id, &outcomes_of_fund);
-            self.event_markets.insert(outcome.event_id, &market);
-            self.investment_funds.insert(fund_id, &fund);
+            // redeeming 40% of the fund hands bob 40% of its open position as a claim
+            set_caller(accounts().bob);
+            contract.redeem(fund_id, 400).unwrap();
+            assert_eq!(PSP37::balance_of(&contract, accounts().bob, Some(0)), 4);
+            // the 6 supplies the fund still holds aren't anyone's balance
+            assert_eq!(PSP37::total_supply(&contract, Some(0)), 4);
+            assert_eq!(PSP37::total_supply(&contract, Some(1)), 0);
 
-            Ok(())
+            assert_eq!(
+                PSP37::transfer(&mut contract, AccountId::from([0u8; 32]), 0, 2, Vec::new()),
+                Err(PSP37Error::TransferToZeroAddress)
+            );
+            PSP37::transfer(&mut contract, accounts().charlie, 0, 2, Vec::new()).unwrap();
+            assert_eq!(PSP37::balance_of(&contract, accounts().charlie, Some(0)), 2);
+            assert_eq!(PSP37::total_supply(&contract, Some(0)), 4);
         }
 
-        #[ink(message)]
-        fn create_proposal(
-            &mut self,
-            fund_id: InvestmentFundId,
-            amount: Share,
-            price: Balance,
-            duration: Option<Timestamp>,
-            proposed_person: Option<AccountId>,
-        ) -> Result<TradeId> {
-            let caller = self.env().caller();
-            let _ = self.get_fund_by_id(fund_id)?;
+        #[ink::test]
+        fn token_messages_only_debit_what_they_use() {
+            let mut contract = new_contract();
+            let token = accounts().django;
+            contract.set_collateral_allowed(token, true).unwrap();
+            contract
+                .owner_token_to_credit
+                .insert((accounts().alice, token), &(3 * MIN_FUND_DEPOSIT));
 
-            let caller_share = self
-                .fund_owner_to_shares
-                .get((fund_id, caller))
-                .unwrap_or_default();
+            let metadata = || FundMetadata {
+                name: None,
+                image_url: None,
+            };
+            assert_eq!(
+                contract.create_fund(100, metadata(), Some(token), 4 * MIN_FUND_DEPOSIT),
+                Err(Error::DepositTooLow)
+            );
 
-            if caller_share < amount {
-                return Err(Error::NotEnoughShare);
-            }
+            let fund_id = contract
+                .create_fund(100, metadata(), Some(token), MIN_FUND_DEPOSIT)
+                .unwrap();
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().total_fund,
+                MIN_FUND_DEPOSIT
+            );
+            assert_eq!(
+                contract.get_collateral_credit(accounts().alice, token),
+                2 * MIN_FUND_DEPOSIT
+            );
 
-            let close_time = self.env().block_timestamp() + duration.unwrap_or(DEFAULT_DURATION);
+            // attaching native value to a token fund is still refused
+            set_value(1);
+            assert_eq!(
+                contract.subscribe(fund_id, MIN_FUND_DEPOSIT),
+                Err(Error::CollateralMismatch)
+            );
+        }
 
-            let new_trade =
-                self.new_trade(fund_id, caller, proposed_person, amount, price, close_time);
+        #[ink::test]
+        fn complete_sets_mint_and_redeem_at_the_set_price() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            let pool = contract.get_event_by_id(event_id).unwrap().1.pool;
 
-            let mut fund_trades = self.fund_to_trades.get(fund_id).unwrap_or(Vec::new());
-            let mut proponent_trades = self.proponent_to_trades.get(caller).unwrap_or(Vec::new());
+            set_caller(accounts().charlie);
+            assert_eq!(
+                contract.mint_complete_set(event_id, 0),
+                Err(Error::ZeroAmount)
+            );
 
-            fund_trades.push(new_trade.trade_id);
-            proponent_trades.push(new_trade.trade_id);
+            // a set costs one supply of each outcome, anything attached above it is refunded
+            set_value(10 * 2_000 + 500);
+            assert_eq!(contract.mint_complete_set(event_id, 10), Ok(20_000));
+            set_value(0);
+            assert_eq!(
+                PSP37::balance_of(&contract, accounts().charlie, Some(0)),
+                10
+            );
+            assert_eq!(
+                PSP37::balance_of(&contract, accounts().charlie, Some(1)),
+                10
+            );
+            assert_eq!(
+                contract.get_event_by_id(event_id).unwrap().1.pool,
+                pool + 20_000
+            );
 
-            self.fund_trades.insert(new_trade.trade_id, &new_trade);
-            self.fund_to_trades.insert(fund_id, &fund_trades);
-            self.proponent_to_trades.insert(caller, &proponent_trades);
+            assert_eq!(
+                contract.redeem_complete_set(event_id, 0),
+                Err(Error::ZeroAmount)
+            );
+            assert_eq!(contract.redeem_complete_set(event_id, 4), Ok(8_000));
+            assert_eq!(PSP37::balance_of(&contract, accounts().charlie, Some(0)), 6);
+            assert_eq!(
+                contract.get_event_by_id(event_id).unwrap().1.pool,
+                pool + 12_000
+            );
+            assert!(contract.redeem_complete_set(event_id, 7).is_err());
 
-            Ok(new_trade.trade_id)
+            propose_and_finalize(&mut contract, event_id, 0);
+            set_caller(accounts().charlie);
+            set_value(2_000);
+            assert_eq!(
+                contract.mint_complete_set(event_id, 1),
+                Err(Error::MarketNotOpen)
+            );
+            set_value(0);
+            assert_eq!(
+                contract.redeem_complete_set(event_id, 1),
+                Err(Error::MarketNotOpen)
+            );
         }
 
-        #[ink(message, payable)]
-        fn accept_proposal(&mut self, trade_id: TradeId) -> Result<()> {
-            let caller = self.env().caller();
-            let transferred_value = self.env().transferred_value();
+        #[ink::test]
+        fn minted_sets_and_fund_bets_are_paid_out_of_the_pool() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
 
-            let mut trade = self.get_trade_by_id(trade_id)?;
+            set_caller(accounts().charlie);
+            set_value(5 * 2_000);
+            contract.mint_complete_set(event_id, 5).unwrap();
+            set_value(0);
 
-            if trade.is_completed {
-                return Err(Error::TradeNotAvailable);
-            }
-            if self.env().block_timestamp() > trade.close_time {
-                return Err(Error::TimeExpired);
-            }
-            if trade.proposed_person.is_some() && caller != trade.proposed_person.unwrap() {
-                return Err(Error::NotOwner);
-            }
-            if transferred_value < trade.price {
-                return Err(Error::DepositTooLow);
-            }
+            let pool = contract.get_event_by_id(event_id).unwrap().1.pool;
+            let total_fund = contract.get_fund_by_id(fund_id).unwrap().total_fund;
+            propose_and_finalize(&mut contract, event_id, 0);
 
-            let proponent_share = self
-                .fund_owner_to_shares
-                .get((trade.investment_fund_id, trade.proponent))
-                .unwrap_or_default();
+            // the fund's 10 supplies and charlie's 5 split the whole pool
+            let prize_per_supply = pool / 15;
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert_eq!(market.prize_per_supply, prize_per_supply);
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().total_fund,
+                total_fund + 10 * prize_per_supply
+            );
 
-            if proponent_share < trade.share {
-                return Err(Error::NotEnoughShare);
+            set_caller(accounts().charlie);
+            assert_eq!(contract.claim_redemption(0), Ok(5 * prize_per_supply));
+            assert_eq!(contract.claim_redemption(1), Ok(0));
+            assert_eq!(contract.claim_redemption(0), Err(Error::NothingToClaim));
+            assert_eq!(
+                contract.get_event_by_id(event_id).unwrap().1.pool,
+                pool - 15 * prize_per_supply
+            );
+        }
+
+        #[ink::test]
+        fn scalar_values_outside_the_range_are_clamped() {
+            let mut contract = new_contract();
+            for (value, long_wins) in [(5, false), (10, false), (110, true), (500, true)] {
+                set_caller(accounts().alice);
+                let event_id = create_scalar_event(&mut contract);
+                let outcomes = contract.get_event_outcomes(event_id).unwrap();
+                set_caller(accounts().bob);
+                let fund_id = create_fund(&mut contract, 100);
+                bet(&mut contract, outcomes[0].0.outcome_id, fund_id, 10);
+                bet(&mut contract, outcomes[1].0.outcome_id, fund_id, 10);
+                let pool = contract.get_event_by_id(event_id).unwrap().1.pool;
+
+                contract.resolve_scalar_event(event_id, value).unwrap();
+
+                let scalar = contract
+                    .get_event_by_id(event_id)
+                    .unwrap()
+                    .1
+                    .scalar
+                    .unwrap();
+                let (long_prize, short_prize) = if long_wins {
+                    (pool / 10, 0)
+                } else {
+                    (0, pool / 10)
+                };
+                assert_eq!(scalar.resolved_value, Some(value));
+                assert_eq!(scalar.long_prize_per_supply, long_prize);
+                assert_eq!(scalar.short_prize_per_supply, short_prize);
             }
+        }
 
-            let caller_share = self
-                .fund_owner_to_shares
-                .get((trade.investment_fund_id, caller))
-                .unwrap_or_default();
+        #[ink::test]
+        fn scalar_pool_splits_linearly_and_a_fund_on_both_sides_is_paid_once() {
+            let mut contract = new_contract();
+            let event_id = create_scalar_event(&mut contract);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+            bet(&mut contract, 1, fund_id, 20);
+            let pool = contract.get_event_by_id(event_id).unwrap().1.pool;
+            let total_fund = contract.get_fund_by_id(fund_id).unwrap().total_fund;
 
-            let mut fund = self.get_fund_by_id(trade.investment_fund_id)?;
+            // 35 is a quarter of the way from 10 to 110
+            contract.resolve_scalar_event(event_id, 35).unwrap();
+
+            let long_prize = pool / 4 / 10;
+            let short_prize = (pool - pool / 4) / 20;
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            let scalar = market.scalar.unwrap();
+            assert!(market.status == MarketStatus::Resolved);
+            assert_eq!(scalar.long_prize_per_supply, long_prize);
+            assert_eq!(scalar.short_prize_per_supply, short_prize);
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().total_fund,
+                total_fund + 10 * long_prize + 20 * short_prize
+            );
+        }
+
+        #[ink::test]
+        fn scalar_side_nobody_holds_goes_back_to_the_owner() {
+            let mut contract = new_contract();
+            let event_id = create_scalar_event(&mut contract);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+            let pool = contract.get_event_by_id(event_id).unwrap().1.pool;
+            let owner_balance =
+                test::get_account_balance::<MyEnvironment>(accounts().alice).unwrap();
+
+            contract.resolve_scalar_event(event_id, 35).unwrap();
+
+            // LONG is only paid its quarter, the rest is not SHORT's to claim
+            let long_prize = pool / 4 / 10;
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert_eq!(market.scalar.unwrap().long_prize_per_supply, long_prize);
+            assert_eq!(market.scalar.unwrap().short_prize_per_supply, 0);
+            assert_eq!(market.pool, 0);
+            assert_eq!(
+                test::get_account_balance::<MyEnvironment>(accounts().alice).unwrap(),
+                owner_balance + pool - 10 * long_prize
+            );
+        }
+
+        #[ink::test]
+        fn scalar_event_nobody_betted_on_is_invalidated() {
+            let mut contract = new_contract();
+            let event_id = create_scalar_event(&mut contract);
+
+            contract.resolve_scalar_event(event_id, 35).unwrap();
+
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert!(market.status == MarketStatus::Invalid);
+            assert!(matches!(
+                emitted().last(),
+                Some(Emitted::MarketCancelled(_))
+            ));
+        }
+
+        #[ink::test]
+        fn sell_pays_the_lmsr_quote_and_clears_an_emptied_position() {
+            let mut contract = new_contract();
+            let event_id = create_event(
+                &mut contract,
+                PricingMode::Lmsr {
+                    liquidity: 100,
+                    payout_per_supply: 1_000_000,
+                },
+            );
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            let cost = contract.quote_bet(0, 10).unwrap().cost;
+            bet(&mut contract, 0, fund_id, 10);
+
+            let pool = contract.get_event_by_id(event_id).unwrap().1.pool;
+            let total_fund = contract.get_fund_by_id(fund_id).unwrap().total_fund;
+            let proceeds = contract.sell(0, fund_id, 4).unwrap();
+            assert!(proceeds > 0 && proceeds < cost);
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().total_fund,
+                total_fund + proceeds
+            );
+            assert_eq!(
+                contract.get_event_by_id(event_id).unwrap().1.pool,
+                pool - proceeds
+            );
+            assert_eq!(contract.get_position(0, fund_id).supply, 6);
+            assert_eq!(
+                contract.get_outcome_by_id(0).unwrap().1.available_supply,
+                994
+            );
+
+            assert_eq!(contract.sell(0, fund_id, 7), Err(Error::NotEnoughSupply));
+            assert_eq!(contract.sell(1, fund_id, 1), Err(Error::NotEnoughSupply));
+
+            // selling back everything gives the bet back, less rounding
+            let rest = contract.sell(0, fund_id, 6).unwrap();
+            assert!((proceeds + rest).abs_diff(cost) <= 2);
+            assert_eq!(contract.get_position(0, fund_id).supply, 0);
+            assert!(!contract
+                .outcome_to_funds
+                .get(0)
+                .unwrap_or_default()
+                .contains(&fund_id));
+            assert!(!contract
+                .fund_to_outcomes
+                .get(fund_id)
+                .unwrap_or_default()
+                .contains(&0));
+        }
+
+        #[ink::test]
+        fn sell_closes_at_the_resolve_date_and_is_refused_on_fixed_markets() {
+            let mut contract = new_contract();
+            let fixed = create_event(&mut contract, PricingMode::Fixed);
+            let lmsr = create_event(
+                &mut contract,
+                PricingMode::Lmsr {
+                    liquidity: 100,
+                    payout_per_supply: 1_000_000,
+                },
+            );
+            let fixed_outcome = contract.get_event_outcomes(fixed).unwrap()[0].0.outcome_id;
+            let lmsr_outcome = contract.get_event_outcomes(lmsr).unwrap()[0].0.outcome_id;
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, fixed_outcome, fund_id, 10);
+            bet(&mut contract, lmsr_outcome, fund_id, 10);
+
+            assert_eq!(
+                contract.sell(fixed_outcome, fund_id, 10),
+                Err(Error::SellNotSupported)
+            );
+
+            set_caller(accounts().charlie);
+            assert_eq!(
+                contract.sell(lmsr_outcome, fund_id, 10),
+                Err(Error::NotOwner)
+            );
+
+            set_caller(accounts().bob);
+            set_now(RESOLVE_DATE - 1);
+            assert!(contract.sell(lmsr_outcome, fund_id, 5).is_ok());
+            set_now(RESOLVE_DATE);
+            assert_eq!(
+                contract.sell(lmsr_outcome, fund_id, 5),
+                Err(Error::ResolveDateNotMatch)
+            );
+        }
+
+        #[ink::test]
+        fn committee_resolves_once_the_threshold_agrees() {
+            let mut contract = new_contract();
+            let members = vec![accounts().bob, accounts().charlie, accounts().django];
+            let event_id = create_committee_event(&mut contract, RESOLVE_DATE, members, 2).unwrap();
+            set_caller(accounts().eve);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 10);
+
+            set_caller(accounts().bob);
+            set_now(RESOLVE_DATE - 1);
+            assert_eq!(
+                contract.vote_resolution(event_id, 0),
+                Err(Error::ResolveDateNotMatch)
+            );
+
+            set_now(RESOLVE_DATE);
+            set_caller(accounts().frank);
+            assert_eq!(
+                contract.vote_resolution(event_id, 0),
+                Err(Error::NotCommitteeMember)
+            );
+
+            // a member can change their mind, only the latest vote counts
+            set_caller(accounts().bob);
+            contract.vote_resolution(event_id, 1).unwrap();
+            assert_eq!(
+                contract.get_resolution_votes(event_id),
+                Ok(vec![(0, 0), (1, 1)])
+            );
+            contract.vote_resolution(event_id, 0).unwrap();
+            assert_eq!(
+                contract.get_resolution_votes(event_id),
+                Ok(vec![(0, 1), (1, 0)])
+            );
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert!(market.status == MarketStatus::Open);
+
+            set_caller(accounts().charlie);
+            contract.vote_resolution(event_id, 0).unwrap();
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert!(market.status == MarketStatus::Resolved);
+            assert_eq!(market.winning_outcome, Some(0));
+
+            set_caller(accounts().django);
+            assert_eq!(
+                contract.vote_resolution(event_id, 1),
+                Err(Error::ResolveDateNotMatch)
+            );
+        }
+
+        #[ink::test]
+        fn committees_need_distinct_members_and_a_reachable_threshold() {
+            let mut contract = new_contract();
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+
+            assert_eq!(
+                create_committee_event(&mut contract, RESOLVE_DATE, vec![bob, charlie, bob], 2),
+                Err(Error::InvalidCommittee)
+            );
+            assert_eq!(
+                create_committee_event(&mut contract, RESOLVE_DATE, vec![bob, charlie], 0),
+                Err(Error::InvalidCommittee)
+            );
+            assert_eq!(
+                create_committee_event(&mut contract, RESOLVE_DATE, vec![bob, charlie], 3),
+                Err(Error::InvalidCommittee)
+            );
+            assert!(
+                create_committee_event(&mut contract, RESOLVE_DATE, vec![bob, charlie], 2).is_ok()
+            );
+        }
+
+        #[ink::test]
+        fn anyone_can_cancel_once_the_committee_timed_out() {
+            let mut contract = new_contract();
+            let event_id =
+                create_committee_event(&mut contract, RESOLVE_DATE, vec![accounts().bob], 1)
+                    .unwrap();
+
+            set_caller(accounts().frank);
+            set_now(RESOLVE_DATE + COMMITTEE_TIMEOUT - 1);
+            assert_eq!(contract.cancel_event(event_id), Err(Error::NotOwner));
+
+            set_now(RESOLVE_DATE + COMMITTEE_TIMEOUT);
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.vote_resolution(event_id, 0),
+                Err(Error::ResolveDateNotMatch)
+            );
+            set_caller(accounts().frank);
+            contract.cancel_event(event_id).unwrap();
+            let market = contract.get_event_by_id(event_id).unwrap().1;
+            assert!(market.status == MarketStatus::Invalid);
+
+            // a resolve date at the end of time never times out instead of overflowing
+            set_caller(accounts().alice);
+            let event_id =
+                create_committee_event(&mut contract, Timestamp::MAX, vec![accounts().bob], 1)
+                    .unwrap();
+            set_caller(accounts().frank);
+            assert_eq!(contract.cancel_event(event_id), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn redeem_pays_a_pro_rata_share_of_cash_and_positions() {
+            let mut contract = new_contract();
+            create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().charlie, 400)
+                .unwrap();
+            bet(&mut contract, 0, fund_id, 10);
+            let total_fund = contract.get_fund_by_id(fund_id).unwrap().total_fund;
+
+            set_caller(accounts().charlie);
+            assert_eq!(contract.redeem(fund_id, 0), Err(Error::NotEnoughShare));
+            assert_eq!(contract.redeem(fund_id, 401), Err(Error::NotEnoughShare));
+
+            let balance = test::get_account_balance::<MyEnvironment>(accounts().charlie).unwrap();
+            let amount = contract.redeem(fund_id, 200).unwrap();
+            assert_eq!(amount, total_fund / 5);
+            assert_eq!(
+                test::get_account_balance::<MyEnvironment>(accounts().charlie).unwrap(),
+                balance + amount
+            );
+            // a fifth of the open position is carved out as charlie's claim
+            assert_eq!(PSP37::balance_of(&contract, accounts().charlie, Some(0)), 2);
+            assert_eq!(contract.get_position(0, fund_id).supply, 8);
+            let fund = contract.get_fund_by_id(fund_id).unwrap();
+            assert_eq!(fund.total_share, 800);
+            assert_eq!(fund.total_fund, total_fund - amount);
+        }
+
+        #[ink::test]
+        fn trader_cannot_redeem_below_51_percent_of_the_rest() {
+            let mut contract = new_contract();
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().charlie, 400)
+                .unwrap();
+
+            // 200 of the remaining 400 would leave bob at 50%
+            assert_eq!(
+                contract.redeem(fund_id, 400),
+                Err(Error::TraderNotIdentitied)
+            );
+            assert_eq!(
+                contract.redeem(fund_id, 600),
+                Err(Error::TraderNotIdentitied)
+            );
+            assert!(contract.redeem(fund_id, 100).is_ok());
+
+            // with nobody else left the trader can take everything out
+            set_caller(accounts().charlie);
+            contract.redeem(fund_id, 400).unwrap();
+            set_caller(accounts().bob);
+            assert!(contract.redeem(fund_id, 500).is_ok());
+        }
+
+        #[ink::test]
+        fn claim_redemption_refunds_the_cost_basis_of_an_invalid_market() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().charlie, 400)
+                .unwrap();
+            bet(&mut contract, 0, fund_id, 10);
+
+            set_caller(accounts().charlie);
+            contract.redeem(fund_id, 400).unwrap();
+            assert_eq!(
+                contract.claim_redemption(0),
+                Err(Error::ResolveDateNotMatch)
+            );
+            assert_eq!(contract.claim_redemption(1), Err(Error::NothingToClaim));
+
+            set_caller(accounts().alice);
+            contract.cancel_event(event_id).unwrap();
+
+            // 4 of the 10 supplies, at the 1_000 each they cost
+            set_caller(accounts().charlie);
+            let available = contract.get_outcome_by_id(0).unwrap().1.available_supply;
+            assert_eq!(contract.claim_redemption(0), Ok(4_000));
+            assert_eq!(
+                contract.get_outcome_by_id(0).unwrap().1.available_supply,
+                available + 4
+            );
+            assert_eq!(contract.claim_redemption(0), Err(Error::NothingToClaim));
+        }
+
+        #[ink::test]
+        fn fixed_positions_are_valued_at_their_stake_share_of_the_pool() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            bet(&mut contract, 0, fund_id, 30);
+            set_caller(accounts().charlie);
+            let other_fund = create_fund(&mut contract, 100);
+            bet(&mut contract, 1, other_fund, 10);
+
+            // 30_000 of the 40_000 staked is on outcome 0, which would split the whole pool
+            let pool = contract.get_event_by_id(event_id).unwrap().1.pool;
+            let market_value = 30 * (pool / 30) * 3 / 4;
+            let valuation = contract.get_fund_nav(fund_id).unwrap();
+            let position = &valuation.positions[0];
+            assert_eq!(position.outcome_id, 0);
+            assert_eq!(position.supply, 30);
+            assert_eq!(position.cost_basis, 30_000);
+            assert_eq!(position.market_value, market_value);
+            assert_eq!(position.mark_price, market_value / 30);
+            assert_eq!(position.unrealized_pnl, market_value as i128 - 30_000);
+            assert_eq!(valuation.liquid_balance, MIN_FUND_DEPOSIT - 30_000);
+            assert_eq!(
+                valuation.net_asset_value,
+                MIN_FUND_DEPOSIT - 30_000 + market_value
+            );
+            assert_eq!(valuation.nav_per_share, valuation.net_asset_value / 100);
+
+            let listed = contract.get_funds(None).unwrap();
+            assert_eq!(listed[fund_id as usize].3, valuation.nav_per_share);
+
+            // an invalid market is only worth the refund
+            set_caller(accounts().alice);
+            contract.cancel_event(event_id).unwrap();
+            let valuation = contract.get_fund_nav(fund_id).unwrap();
+            assert_eq!(valuation.positions[0].market_value, 30_000);
+            assert_eq!(valuation.positions[0].unrealized_pnl, 0);
+            assert_eq!(valuation.net_asset_value, MIN_FUND_DEPOSIT);
+        }
+
+        #[ink::test]
+        fn lmsr_positions_are_valued_at_the_implied_price() {
+            let mut contract = new_contract();
+            let event_id = create_event(
+                &mut contract,
+                PricingMode::Lmsr {
+                    liquidity: 100,
+                    payout_per_supply: 1_000_000,
+                },
+            );
+            set_caller(accounts().bob);
+            let fund_id = create_fund(&mut contract, 100);
+            let cost = contract.quote_bet(0, 10).unwrap().cost;
+            bet(&mut contract, 0, fund_id, 10);
+
+            let price = math::lmsr_prices(&[10, 0], 100).unwrap()[0];
+            assert!(price > PROBABILITY_ONE / 2);
+            let market_value = math::mul_div(price, 10 * 1_000_000, PROBABILITY_ONE).unwrap();
+
+            let valuation = contract.get_fund_nav(fund_id).unwrap();
+            let position = &valuation.positions[0];
+            assert_eq!(position.market_value, market_value);
+            assert_eq!(position.mark_price, market_value / 10);
+            assert_eq!(position.unrealized_pnl, market_value as i128 - cost as i128);
+            assert_eq!(
+                valuation.net_asset_value,
+                MIN_FUND_DEPOSIT - cost + market_value
+            );
+            assert_eq!(
+                contract.get_funds(Some(accounts().bob)).unwrap()[0].3,
+                valuation.net_asset_value / 100
+            );
+
+            // once resolved the prize is in the liquid balance and the position drops out
+            propose_and_finalize(&mut contract, event_id, 0);
+            let valuation = contract.get_fund_nav(fund_id).unwrap();
+            assert!(valuation.positions.is_empty());
+            assert_eq!(valuation.net_asset_value, valuation.liquid_balance);
+            assert_eq!(
+                valuation.liquid_balance,
+                MIN_FUND_DEPOSIT - cost + 10 * 1_000_000
+            );
+        }
+
+        #[ink::test]
+        fn proposed_shares_are_escrowed_and_paid_out_to_the_buyer() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
+
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.create_proposal(fund_id, 401, 1_000, 1, None, None),
+                Err(Error::NotEnoughShare)
+            );
+            let trade_id = contract
+                .create_proposal(fund_id, 100, 1_000, 100, None, None)
+                .unwrap();
+            assert_eq!(contract.get_owner_share(fund_id, accounts().bob), Ok(300));
+            assert_eq!(contract.get_fund_by_id(fund_id).unwrap().total_share, 1_000);
+            // escrowed shares can't be offered twice
+            assert_eq!(
+                contract.create_proposal(fund_id, 301, 1_000, 1, None, None),
+                Err(Error::NotEnoughShare)
+            );
+
+            let balance = test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap();
+            set_caller(accounts().charlie);
+            set_value(100 * 1_000);
+            contract.accept_proposal(trade_id, 100).unwrap();
+            set_value(0);
+
+            assert_eq!(
+                contract.get_owner_share(fund_id, accounts().charlie),
+                Ok(100)
+            );
+            assert_eq!(contract.get_owner_share(fund_id, accounts().bob), Ok(300));
+            assert_eq!(
+                test::get_account_balance::<MyEnvironment>(accounts().bob).unwrap(),
+                balance + 100 * 1_000
+            );
+            assert!(contract.get_trade_by_id(trade_id).unwrap().is_completed);
+        }
+
+        #[ink::test]
+        fn cancelling_a_proposal_returns_the_unsold_escrow() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().bob, 400)
+                .unwrap();
+            set_caller(accounts().bob);
+            let trade_id = contract
+                .create_proposal(fund_id, 200, 1_000, 50, None, None)
+                .unwrap();
+
+            set_caller(accounts().charlie);
+            set_value(50 * 1_000);
+            contract.accept_proposal(trade_id, 50).unwrap();
+            set_value(0);
+            assert_eq!(contract.cancel_proposal(trade_id), Err(Error::NotOwner));
+
+            set_caller(accounts().bob);
+            contract.cancel_proposal(trade_id).unwrap();
+            assert_eq!(contract.get_owner_share(fund_id, accounts().bob), Ok(350));
+            assert_eq!(
+                contract.cancel_proposal(trade_id),
+                Err(Error::TradeNotAvailable)
+            );
+
+            set_caller(accounts().charlie);
+            set_value(50 * 1_000);
+            assert_eq!(
+                contract.accept_proposal(trade_id, 50),
+                Err(Error::TradeNotAvailable)
+            );
+        }
 
-            if trade.proponent == fund.trader {
-                if ((trade.share as i64 * 100) / fund.total_share as i64) >= 51 {
-                    fund.trader = caller;
-                } else if (((proponent_share - trade.share) * 100 / fund.total_share) < 51)
-                    && (((caller_share + trade.share) * 100 / fund.total_share) < 51)
-                {
-                    return Err(Error::TraderNotIdentitied);
-                }
-            }
+        #[ink::test]
+        fn only_the_proponent_amends_a_fixed_price_proposal() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            let trade_id = contract
+                .create_proposal(fund_id, 100, 1_000, 100, None, None)
+                .unwrap();
 
-            trade.is_completed = true;
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.update_proposal(trade_id, 500, RESOLVE_DATE, None),
+                Err(Error::NotOwner)
+            );
 
-            self.fund_trades.insert(trade_id, &trade);
-            self.fund_owner_to_shares.insert(
-                (trade.investment_fund_id, trade.proponent),
-                &(proponent_share - trade.share),
+            set_caller(accounts().alice);
+            set_now(10);
+            assert_eq!(
+                contract.update_proposal(trade_id, 500, 10, None),
+                Err(Error::TimeExpired)
             );
-            self.fund_owner_to_shares.insert(
-                (trade.investment_fund_id, caller),
-                &(caller_share + trade.share),
+            contract
+                .update_proposal(trade_id, 500, RESOLVE_DATE, Some(accounts().bob))
+                .unwrap();
+            let trade = contract.get_trade_by_id(trade_id).unwrap();
+            assert_eq!(trade.price_per_share, 500);
+            assert_eq!(trade.close_time, RESOLVE_DATE);
+            assert_eq!(trade.proposed_person, Some(accounts().bob));
+
+            // an auction's terms are fixed once it started
+            let auction_id = contract
+                .create_auction(fund_id, 50, 1_000, None, english_auction())
+                .unwrap();
+            assert_eq!(
+                contract.update_proposal(auction_id, 500, RESOLVE_DATE, None),
+                Err(Error::TradeNotAvailable)
             );
-            self.investment_funds
-                .insert(trade.investment_fund_id, &fund);
-            Ok(())
         }
 
-        #[ink(message)]
-        fn get_funds(
-            &self,
-            owner: Option<AccountId>,
-        ) -> Result<Vec<(InvestmentFund, Vec<(OutCome, Supply)>, Option<Share>)>> {
-            let mut rs = Vec::new();
+        #[ink::test]
+        fn proposals_are_filtered_by_status() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            let open = contract
+                .create_proposal(fund_id, 10, 1_000, 10, None, None)
+                .unwrap();
+            let completed = contract
+                .create_proposal(fund_id, 10, 1_000, 10, None, None)
+                .unwrap();
+            let cancelled = contract
+                .create_proposal(fund_id, 10, 1_000, 10, None, None)
+                .unwrap();
+            let expired = contract
+                .create_proposal(fund_id, 10, 1_000, 10, Some(10), None)
+                .unwrap();
 
-            for i in 0..self.next_fund_id {
-                let fund = self.get_fund_by_id(i)?;
-                let fund_outcomes = self.get_fund_outcomes(i)?;
-                match owner {
-                    Some(owner) => {
-                        let owner_share = self
-                            .fund_owner_to_shares
-                            .get((fund.investment_fund_id, owner))
-                            .unwrap_or_default();
+            contract.cancel_proposal(cancelled).unwrap();
+            set_caller(accounts().bob);
+            set_value(10 * 1_000);
+            contract.accept_proposal(completed, 10).unwrap();
+            set_value(0);
+            set_now(11);
 
-                        if owner_share != 0 {
-                            rs.push((fund, fund_outcomes, Some(owner_share)));
-                        }
-                    }
-                    None => rs.push((fund, fund_outcomes, None)),
-                }
+            for (status, trade_id) in [
+                (TradeStatus::Open, open),
+                (TradeStatus::Completed, completed),
+                (TradeStatus::Cancelled, cancelled),
+                (TradeStatus::Expired, expired),
+            ] {
+                let listed: Vec<TradeId> = contract
+                    .get_proposals(None, Some(status))
+                    .unwrap()
+                    .iter()
+                    .map(|(_, trade)| trade.trade_id)
+                    .collect();
+                assert_eq!(listed, vec![trade_id]);
+
+                let listed: Vec<TradeId> = contract
+                    .get_fund_proposals(fund_id, Some(status))
+                    .unwrap()
+                    .1
+                    .iter()
+                    .map(|trade| trade.trade_id)
+                    .collect();
+                assert_eq!(listed, vec![trade_id]);
             }
 
-            Ok(rs)
+            assert_eq!(
+                contract
+                    .get_proposals(Some(accounts().alice), None)
+                    .unwrap()
+                    .len(),
+                4
+            );
+            assert!(contract
+                .get_proposals(Some(accounts().bob), None)
+                .unwrap()
+                .is_empty());
         }
 
-        #[ink(message)]
-        fn get_owner_share(&self, fund_id: InvestmentFundId, owner: AccountId) -> Result<Share> {
-            let owner_share = self
-                .fund_owner_to_shares
-                .get((fund_id, owner))
-                .unwrap_or_default();
+        #[ink::test]
+        fn share_transfer_from_spends_the_allowance_and_can_hand_over_control() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            let zero = AccountId::from([0u8; 32]);
+            PSP22MultiAsset::approve(&mut contract, fund_id, accounts().bob, 600).unwrap();
 
-            Ok(owner_share)
-        }
+            set_caller(accounts().bob);
+            assert_eq!(
+                PSP22MultiAsset::transfer_from(
+                    &mut contract,
+                    fund_id,
+                    accounts().alice,
+                    accounts().charlie,
+                    700,
+                    Vec::new()
+                ),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+            assert_eq!(
+                PSP22MultiAsset::transfer_from(
+                    &mut contract,
+                    fund_id,
+                    accounts().alice,
+                    zero,
+                    600,
+                    Vec::new()
+                ),
+                Err(PSP22Error::ZeroRecipientAddress)
+            );
+            assert_eq!(
+                PSP22MultiAsset::transfer_from(
+                    &mut contract,
+                    fund_id,
+                    zero,
+                    accounts().charlie,
+                    600,
+                    Vec::new()
+                ),
+                Err(PSP22Error::ZeroSenderAddress)
+            );
 
-        #[ink(message)]
-        fn get_proposals(
-            &self,
-            proponent: Option<AccountId>,
-        ) -> Result<Vec<(InvestmentFund, FundTrade)>> {
-            let mut rs = Vec::new();
-            match proponent {
-                Some(id) => {
-                    let trade_ids = self.proponent_to_trades.get(id).unwrap_or(Vec::new());
+            // 60% is enough for charlie to take over as the trader
+            PSP22MultiAsset::transfer_from(
+                &mut contract,
+                fund_id,
+                accounts().alice,
+                accounts().charlie,
+                600,
+                Vec::new(),
+            )
+            .unwrap();
+            assert_eq!(
+                PSP22MultiAsset::allowance(&contract, fund_id, accounts().alice, accounts().bob),
+                0
+            );
+            assert_eq!(
+                PSP22MultiAsset::balance_of(&contract, fund_id, accounts().charlie),
+                600
+            );
+            assert_eq!(
+                PSP22MultiAsset::balance_of(&contract, fund_id, accounts().alice),
+                400
+            );
+            assert_eq!(
+                contract.get_fund_by_id(fund_id).unwrap().trader,
+                accounts().charlie
+            );
+            assert!(matches!(emitted().last(), Some(Emitted::TraderChanged(_))));
+            assert_eq!(
+                PSP22MultiAsset::transfer_from(
+                    &mut contract,
+                    fund_id,
+                    accounts().alice,
+                    accounts().bob,
+                    1,
+                    Vec::new()
+                ),
+                Err(PSP22Error::InsufficientAllowance)
+            );
 
-                    for trade_id in trade_ids.into_iter() {
-                        let fund = self.investment_funds.get(trade_id).unwrap();
-                        let trade = self.get_trade_by_id(trade_id)?;
+            // nobody can hand the fund to the zero account
+            set_caller(accounts().charlie);
+            assert_eq!(
+                PSP22MultiAsset::transfer(&mut contract, fund_id, zero, 600, Vec::new()),
+                Err(PSP22Error::ZeroRecipientAddress)
+            );
+        }
 
-                        rs.push((fund, trade));
-                    }
-                }
-                None => {
-                    for trade_id in 0..self.next_trade_id {
-                        let fund = self.investment_funds.get(trade_id).unwrap();
-                        let trade = self.get_trade_by_id(trade_id)?;
+        #[ink::test]
+        fn share_total_supply_leaves_out_escrowed_shares() {
+            let mut contract = new_contract();
+            let fund_id = create_fund(&mut contract, 1_000);
+            contract
+                .transfer_share(fund_id, accounts().bob, 300)
+                .unwrap();
+            assert_eq!(PSP22MultiAsset::total_supply(&contract, fund_id), 1_000);
 
-                        rs.push((fund, trade));
-                    }
-                }
-            }
+            contract
+                .create_proposal(fund_id, 50, 1_000, 50, None, None)
+                .unwrap();
+            set_caller(accounts().bob);
+            contract.place_ask(fund_id, 100, MIN_ORDER_VALUE).unwrap();
 
-            Ok(rs)
+            let balances = PSP22MultiAsset::balance_of(&contract, fund_id, accounts().alice)
+                + PSP22MultiAsset::balance_of(&contract, fund_id, accounts().bob);
+            assert_eq!(balances, 850);
+            assert_eq!(PSP22MultiAsset::total_supply(&contract, fund_id), balances);
         }
 
-        #[ink(message)]
-        fn get_fund_proposals(
-            &self,
-            fund_id: InvestmentFundId,
-        ) -> Result<(InvestmentFund, Vec<FundTrade>)> {
-            let fund = self.get_fund_by_id(fund_id)?;
-            let trade_ids = self.fund_to_trades.get(fund_id).unwrap_or(Vec::new());
-            let mut trades = Vec::new();
+        #[ink::test]
+        fn complete_sets_close_at_the_resolve_date() {
+            let mut contract = new_contract();
+            let event_id = create_event(&mut contract, PricingMode::Fixed);
 
-            for trade_id in trade_ids.into_iter() {
-                trades.push(self.get_trade_by_id(trade_id)?);
-            }
+            set_caller(accounts().charlie);
+            set_now(RESOLVE_DATE - 1);
+            set_value(2 * 2_000);
+            assert_eq!(contract.mint_complete_set(event_id, 2), Ok(4_000));
+            set_value(0);
+            assert_eq!(contract.redeem_complete_set(event_id, 1), Ok(2_000));
 
-            Ok((fund, trades))
+            set_now(RESOLVE_DATE);
+            set_value(2_000);
+            assert_eq!(
+                contract.mint_complete_set(event_id, 1),
+                Err(Error::MarketNotOpen)
+            );
+            set_value(0);
+            assert_eq!(
+                contract.redeem_complete_set(event_id, 1),
+                Err(Error::MarketNotOpen)
+            );
+
+            // nor can a set be redeemed while a resolution is pending
+            set_caller(accounts().eve);
+            set_value(MIN_RESOLUTION_BOND);
+            contract.propose_resolution(event_id, 0).unwrap();
+            set_value(0);
+            set_caller(accounts().charlie);
+            assert_eq!(
+                contract.redeem_complete_set(event_id, 1),
+                Err(Error::MarketNotOpen)
+            );
         }
     }
 
-    #[ink(impl)]
-    impl PredictionMarket {
-        fn get_event_by_id(&self, event_id: EventId) -> Result<(Event, EventMarket, Supply)> {
-            let event = self.events.get(event_id);
-            let event_markets = self.event_markets.get(event_id);
-            if event.is_none() || event_markets.is_none() {
-                return Err(Error::EventNotFound);
-            }
-            let mut total_supply = 0;
-            for oid in self
-                .event_to_outcomes
-                .get(event_id)
-                .unwrap_or(Vec::new())
-                .into_iter()
-            {
-                total_supply += self.get_outcome_by_id(oid)?.0.total_supply;
-            }
-            Ok((event.unwrap(), event_markets.unwrap(), total_supply))
-        }
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink::env::call::{build_create, ExecutionInput, Selector};
+        use ink_e2e::MessageBuilder;
 
-        fn get_outcome_by_id(&self, outcome_id: OutComeId) -> Result<(OutCome, MarketOutCome)> {
-            let outcome = self.outcomes.get(outcome_id);
-            let market_outcome = self.market_outcomes.get(outcome_id);
-            if outcome.is_none() || market_outcome.is_none() {
-                return Err(Error::WrongEventOutCome);
-            }
-            Ok((outcome.unwrap(), market_outcome.unwrap()))
-        }
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+        type Client = ink_e2e::Client<ink_e2e::PolkadotConfig, MyEnvironment>;
 
-        fn get_fund_by_id(&self, fund_id: InvestmentFundId) -> Result<InvestmentFund> {
-            let fund = self.investment_funds.get(fund_id);
-            if fund.is_none() {
-                return Err(Error::FundNotFound);
-            }
-            Ok(fund.unwrap())
-        }
+        // Long enough for the bets to land before the event is due.
+        const BETTING_TIME: Timestamp = 6_000;
 
-        fn get_trade_by_id(&self, trade_id: TradeId) -> Result<FundTrade> {
-            let trade = self.fund_trades.get(trade_id);
-            if trade.is_none() {
-                return Err(Error::TradeNotFound);
-            }
-            Ok(trade.unwrap())
+        // The mock oracle is built for the default environment, so its constructor is encoded
+        // by selector against ours instead of through its own contract ref.
+        struct MockOracleRef;
+
+        impl ink::env::ContractEnv for MockOracleRef {
+            type Env = MyEnvironment;
         }
 
-        fn get_outcome_funds(
-            &self,
-            outcome_id: OutComeId,
-        ) -> Result<Vec<(InvestmentFund, Supply)>> {
-            let mut rs = Vec::new();
-            for i in self.outcome_to_funds.get(outcome_id).unwrap_or(Vec::new()) {
-                rs.push((
-                    self.investment_funds.get(i).unwrap(),
-                    self.outcome_fund_to_supplies
-                        .get((outcome_id, i))
-                        .unwrap_or_default(),
-                ));
+        impl ink::env::call::FromAccountId<MyEnvironment> for MockOracleRef {
+            fn from_account_id(_: AccountId) -> Self {
+                Self
             }
-            Ok(rs)
         }
 
-        fn get_fund_outcomes(&self, fund_id: InvestmentFundId) -> Result<Vec<(OutCome, Supply)>> {
-            let mut rs = Vec::new();
-            for oid in self.fund_to_outcomes.get(fund_id).unwrap_or(Vec::new()) {
-                rs.push((
-                    self.outcomes.get(oid).unwrap(),
-                    self.outcome_fund_to_supplies
-                        .get((oid, fund_id))
-                        .unwrap_or_default(),
-                ));
-            }
-            Ok(rs)
+        fn now() -> Timestamp {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as Timestamp
         }
 
-        fn get_event_outcomes(&self, event_id: EventId) -> Result<Vec<(OutCome, MarketOutCome)>> {
-            let mut rs = Vec::new();
-            for i in self.event_to_outcomes.get(event_id).unwrap_or(Vec::new()) {
-                rs.push(self.get_outcome_by_id(i)?);
+        fn outcome(description: &str) -> OutComePayload {
+            OutComePayload {
+                description: String::from(description),
+                deposit_per_supply: 1_000,
+                total_supply: 1_000,
             }
-            Ok(rs)
         }
-    }
 
-    #[ink(impl)]
-    impl PredictionMarket {
-        fn new_outcome(&mut self, event_id: EventId, payload: OutComePayload) -> OutCome {
-            let outcome_id = self.next_outcome_id;
-            self.next_outcome_id += 1;
-            let outcome = OutCome {
-                event_id,
-                outcome_id,
-                description: payload.description,
-                deposit_per_supply: payload.deposit_per_supply,
-                total_supply: payload.total_supply,
-            };
-            outcome
+        async fn deploy_market(client: &mut Client) -> AccountId {
+            client
+                .instantiate(
+                    "prediction_market",
+                    &ink_e2e::alice(),
+                    PredictionMarketRef::default(),
+                    0,
+                    None,
+                )
+                .await
+                .expect("instantiate failed")
+                .account_id
         }
 
-        fn new_market_outcome(
-            &mut self,
-            event_id: EventId,
-            outcome_id: OutComeId,
-            supply: Supply,
-        ) -> MarketOutCome {
-            let outcome = MarketOutCome {
-                event_id,
-                outcome_id,
-                available_supply: supply,
-            };
-            outcome
+        async fn deploy_oracle(client: &mut Client, answer: Option<OracleAnswer>) -> AccountId {
+            let constructor = build_create::<MockOracleRef>()
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("new")))
+                        .push_arg(answer),
+                )
+                .returns::<MockOracleRef>();
+            client
+                .instantiate("mock_oracle", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id
         }
 
-        fn new_event(
-            &mut self,
-            owner: AccountId,
-            question: String,
-            bets: Vec<OutComePayload>,
-            metadata: EventMetadata,
-        ) -> Result<(Event, Vec<OutComeId>)> {
-            let mut event_bets: Vec<OutComeId> = Vec::new();
+        // An event whose "Yes" outcome wins for answers below 50 and "No" from 50 up, with a
+        // fund that bet on "Yes". Returns the event and its outcome ids.
+        async fn create_betted_event(
+            client: &mut Client,
+            market: &AccountId,
+            oracle: AccountId,
+        ) -> (EventId, Vec<OutComeId>) {
+            let resolve_date = now() + BETTING_TIME;
+            let create_event =
+                MessageBuilder::<MyEnvironment, PredictionMarketRef>::from_account_id(
+                    market.clone(),
+                )
+                .call(|market| {
+                    market.create_event(
+                        String::from("Will it rain?"),
+                        resolve_date,
+                        vec![outcome("Yes"), outcome("No")],
+                        EventMetadata {
+                            name: None,
+                            image_url: None,
+                            description: None,
+                        },
+                        MarketTerms {
+                            pricing: PricingMode::Fixed,
+                            collateral: None,
+                            deposit: 0,
+                        },
+                        Some(ResolverPayload::Oracle(OracleBindingPayload {
+                            oracle,
+                            query: b"rain".to_vec(),
+                            answers: vec![(0, 0), (50, 1)],
+                        })),
+                    )
+                });
+            let event_id = client
+                .call(&ink_e2e::alice(), create_event, MIN_EVENT_DEPOSIT, None)
+                .await
+                .expect("create_event failed")
+                .return_value()
+                .unwrap();
 
-            let event_id = self.next_event_id;
-            self.next_event_id += 1;
+            let event_detail =
+                MessageBuilder::<MyEnvironment, PredictionMarketRef>::from_account_id(
+                    market.clone(),
+                )
+                .call(|market| market.get_event_detail(event_id));
+            let outcome_ids = client
+                .call_dry_run(&ink_e2e::alice(), &event_detail, 0, None)
+                .await
+                .return_value()
+                .unwrap()
+                .3
+                .iter()
+                .map(|(outcome, _, _)| outcome.outcome_id)
+                .collect::<Vec<_>>();
 
-            if bets.len() < 2 {
-                return Err(Error::AtLeastTwoOutcome);
-            }
+            let create_fund =
+                MessageBuilder::<MyEnvironment, PredictionMarketRef>::from_account_id(
+                    market.clone(),
+                )
+                .call(|market| {
+                    market.create_fund(
+                        1_000,
+                        FundMetadata {
+                            name: None,
+                            image_url: None,
+                        },
+                        None,
+                        0,
+                    )
+                });
+            let fund_id = client
+                .call(&ink_e2e::bob(), create_fund, MIN_FUND_DEPOSIT, None)
+                .await
+                .expect("create_fund failed")
+                .return_value()
+                .unwrap();
 
-            for payload in bets.into_iter() {
-                let outcome_total_supply = payload.total_supply;
+            let quote = MessageBuilder::<MyEnvironment, PredictionMarketRef>::from_account_id(
+                market.clone(),
+            )
+            .call(|market| market.quote_bet(outcome_ids[0], 10));
+            let cost = client
+                .call_dry_run(&ink_e2e::bob(), &quote, 0, None)
+                .await
+                .return_value()
+                .unwrap()
+                .cost;
+            let bet = MessageBuilder::<MyEnvironment, PredictionMarketRef>::from_account_id(
+                market.clone(),
+            )
+            .call(|market| market.bet(outcome_ids[0], fund_id, 10));
+            client
+                .call(&ink_e2e::bob(), bet, cost, None)
+                .await
+                .expect("bet failed")
+                .return_value()
+                .unwrap();
 
-                let new_outcome = self.new_outcome(event_id, payload);
-                let market_outcome =
-                    self.new_market_outcome(event_id, new_outcome.outcome_id, outcome_total_supply);
+            (event_id, outcome_ids)
+        }
 
-                event_bets.push(new_outcome.outcome_id);
-                self.outcomes.insert(new_outcome.outcome_id, &new_outcome);
-                self.market_outcomes
-                    .insert(new_outcome.outcome_id, &market_outcome);
-            }
+        async fn wait_until_due() {
+            ink_e2e::tokio::time::sleep(std::time::Duration::from_millis(BETTING_TIME * 2)).await;
+        }
 
-            Ok((
-                Event {
-                    event_id,
-                    owner,
-                    question,
-                    metadata,
-                },
-                event_bets,
-            ))
+        async fn resolve_from_oracle(
+            client: &mut Client,
+            market: &AccountId,
+            event_id: EventId,
+        ) -> Result<OutComeId> {
+            let resolve = MessageBuilder::<MyEnvironment, PredictionMarketRef>::from_account_id(
+                market.clone(),
+            )
+            .call(|market| market.resolve_from_oracle(event_id));
+            let result = client
+                .call_dry_run(&ink_e2e::charlie(), &resolve, 0, None)
+                .await
+                .return_value();
+            if result.is_ok() {
+                client
+                    .call(&ink_e2e::charlie(), resolve, 0, None)
+                    .await
+                    .expect("resolve_from_oracle failed");
+            }
+            result
         }
 
-        fn new_market(
-            &mut self,
+        async fn market_status(
+            client: &mut Client,
+            market: &AccountId,
             event_id: EventId,
-            deposit: Balance,
-            resolve_date: Timestamp,
-        ) -> Result<EventMarket> {
-            let new_market = EventMarket {
-                event_id,
-                pool: deposit,
-                is_resolved: false,
-                resolve_date,
-                winning_outcome: None,
-            };
+        ) -> MarketStatus {
+            let event_detail =
+                MessageBuilder::<MyEnvironment, PredictionMarketRef>::from_account_id(
+                    market.clone(),
+                )
+                .call(|market| market.get_event_detail(event_id));
+            client
+                .call_dry_run(&ink_e2e::alice(), &event_detail, 0, None)
+                .await
+                .return_value()
+                .unwrap()
+                .1
+                .status
+        }
 
-            Ok(new_market)
+        #[ink_e2e::test(
+            additional_contracts = "mock_oracle/Cargo.toml",
+            environment = crate::MyEnvironment
+        )]
+        async fn oracle_answer_resolves_to_the_matching_outcome(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let market = deploy_market(&mut client).await;
+            let oracle = deploy_oracle(&mut client, Some(10)).await;
+            let (event_id, outcome_ids) = create_betted_event(&mut client, &market, oracle).await;
+
+            wait_until_due().await;
+
+            assert_eq!(
+                resolve_from_oracle(&mut client, &market, event_id).await,
+                Ok(outcome_ids[0])
+            );
+            assert!(market_status(&mut client, &market, event_id).await == MarketStatus::Resolved);
+            Ok(())
         }
 
-        fn new_fund(
-            &mut self,
-            trader: AccountId,
-            total_share: Share,
-            total_fund: Balance,
-            metadata: FundMetadata,
-        ) -> InvestmentFund {
-            let investment_fund_id = self.next_fund_id;
-            self.next_fund_id += 1;
+        #[ink_e2e::test(
+            additional_contracts = "mock_oracle/Cargo.toml",
+            environment = crate::MyEnvironment
+        )]
+        async fn oracle_answer_nobody_betted_on_invalidates_the_market(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let market = deploy_market(&mut client).await;
+            let oracle = deploy_oracle(&mut client, Some(70)).await;
+            let (event_id, outcome_ids) = create_betted_event(&mut client, &market, oracle).await;
 
-            let new_fund = InvestmentFund {
-                investment_fund_id,
-                trader,
-                total_fund,
-                total_share,
-                metadata,
-            };
+            wait_until_due().await;
 
-            new_fund
+            assert_eq!(
+                resolve_from_oracle(&mut client, &market, event_id).await,
+                Ok(outcome_ids[1])
+            );
+            assert!(market_status(&mut client, &market, event_id).await == MarketStatus::Invalid);
+            Ok(())
         }
 
-        fn new_trade(
-            &mut self,
-            fund_id: InvestmentFundId,
-            proponent: AccountId,
-            proposed_person: Option<AccountId>,
-            share: Share,
-            price: Balance,
-            close_time: Timestamp,
-        ) -> FundTrade {
-            let trade_id = self.next_trade_id;
-            self.next_trade_id += 1;
+        #[ink_e2e::test(
+            additional_contracts = "mock_oracle/Cargo.toml",
+            environment = crate::MyEnvironment
+        )]
+        async fn unanswered_oracle_leaves_the_market_open(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let market = deploy_market(&mut client).await;
+            let oracle = deploy_oracle(&mut client, None).await;
+            let (event_id, _) = create_betted_event(&mut client, &market, oracle).await;
 
-            FundTrade {
-                investment_fund_id: fund_id,
-                trade_id,
-                proponent,
-                proposed_person,
-                share,
-                price,
-                close_time,
-                is_completed: false,
-            }
+            assert_eq!(
+                resolve_from_oracle(&mut client, &market, event_id).await,
+                Err(Error::ResolveDateNotMatch)
+            );
+
+            wait_until_due().await;
+
+            assert_eq!(
+                resolve_from_oracle(&mut client, &market, event_id).await,
+                Err(Error::OracleNotReady)
+            );
+            assert!(market_status(&mut client, &market, event_id).await == MarketStatus::Open);
+            Ok(())
         }
     }
 }
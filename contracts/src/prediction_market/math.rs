@@ -0,0 +1,235 @@
+use crate::types::*;
+use ink::prelude::vec::Vec;
+
+// Unsigned fixed-point arithmetic with 18 decimals, usable under `no_std`.
+pub const ONE: u128 = 1_000_000_000_000_000_000;
+pub const LN_2: u128 = 693_147_180_559_945_309;
+const EXP_CUTOFF: u128 = 42 * ONE;
+
+pub fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+    (a / c)
+        .checked_mul(b)?
+        .checked_add((a % c).checked_mul(b)? / c)
+}
+
+pub fn mul_div_up(a: u128, b: u128, c: u128) -> Option<u128> {
+    let rs = mul_div(a, b, c)?;
+    if (a % c).checked_mul(b)? % c != 0 {
+        return rs.checked_add(1);
+    }
+    Some(rs)
+}
+
+// e^(-x)
+pub fn exp_neg(x: u128) -> Option<u128> {
+    if x >= EXP_CUTOFF {
+        return Some(0);
+    }
+    let k = x / LN_2;
+    let r = x.checked_sub(k.checked_mul(LN_2)?)?;
+
+    let mut term = ONE;
+    let mut sum = ONE;
+    for n in 1..=32u128 {
+        term = mul_div(term, r, ONE)? / n;
+        if term == 0 {
+            break;
+        }
+        sum = sum.checked_add(term)?;
+    }
+
+    Some(mul_div(ONE, ONE, sum)? >> k)
+}
+
+// ln(x) for x >= 1
+pub fn ln(x: u128) -> Option<u128> {
+    if x < ONE {
+        return None;
+    }
+    let mut m = x;
+    let mut k = 0u128;
+    while m >= 2 * ONE {
+        m /= 2;
+        k += 1;
+    }
+
+    let z = (m - ONE) * ONE / (m + ONE);
+    let z_squared = z * z / ONE;
+    let mut power = z;
+    let mut sum = 0;
+    let mut n = 1u128;
+    while power != 0 {
+        sum += power / n;
+        power = power * z_squared / ONE;
+        n += 2;
+    }
+
+    Some(k * LN_2 + 2 * sum)
+}
+
+// LMSR cost function C(q) = b * ln(sum(e^(q_i / b))), expressed in supplies scaled by ONE.
+// Evaluated as max(q) + b * ln(sum(e^((q_i - max(q)) / b))) so every exponent stays non-positive.
+pub fn lmsr_cost(quantities: &[Supply], liquidity: Supply) -> Option<u128> {
+    if liquidity == 0 || quantities.is_empty() {
+        return None;
+    }
+    let b = liquidity as u128;
+    let max_q = *quantities.iter().max()? as u128;
+
+    let mut sum = 0u128;
+    for q in quantities {
+        sum = sum.checked_add(exp_neg(mul_div(max_q - *q as u128, ONE, b)?)?)?;
+    }
+
+    max_q
+        .checked_mul(ONE)?
+        .checked_add(b.checked_mul(ln(sum)?)?)
+}
+
+// Instantaneous prices e^(q_i / b) / sum(e^(q_j / b)), scaled by ONE.
+pub fn lmsr_prices(quantities: &[Supply], liquidity: Supply) -> Option<Vec<u128>> {
+    if liquidity == 0 || quantities.is_empty() {
+        return None;
+    }
+    let b = liquidity as u128;
+    let max_q = *quantities.iter().max()? as u128;
+
+    let weights = quantities
+        .iter()
+        .map(|q| exp_neg(mul_div(max_q - *q as u128, ONE, b)?))
+        .collect::<Option<Vec<u128>>>()?;
+    let total = weights
+        .iter()
+        .try_fold(0u128, |total, w| total.checked_add(*w))?;
+
+    weights
+        .into_iter()
+        .map(|w| mul_div(w, ONE, total))
+        .collect()
+}
+
+// Worst case loss of the market maker, b * ln(n), in supplies scaled by ONE.
+pub fn lmsr_max_loss(outcomes: usize, liquidity: Supply) -> Option<u128> {
+    (liquidity as u128).checked_mul(ln((outcomes as u128).checked_mul(ONE)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // e^-1 to 18 decimals
+    const EXP_NEG_ONE: u128 = 367_879_441_171_442_321;
+
+    fn assert_close(actual: u128, expected: u128, tolerance: u128) {
+        assert!(
+            actual.abs_diff(expected) <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn exp_neg_matches_known_values() {
+        assert_eq!(exp_neg(0), Some(ONE));
+        assert_close(exp_neg(ONE).unwrap(), EXP_NEG_ONE, 1_000);
+        assert_close(exp_neg(LN_2).unwrap(), ONE / 2, 1_000);
+        assert_close(exp_neg(10 * LN_2).unwrap(), ONE / 1024, 1_000);
+        assert_eq!(exp_neg(EXP_CUTOFF), Some(0));
+        assert_eq!(exp_neg(u128::MAX), Some(0));
+    }
+
+    #[test]
+    fn exp_neg_is_decreasing() {
+        let mut previous = exp_neg(0).unwrap();
+        for step in 1..=100u128 {
+            let current = exp_neg(step * ONE / 4).unwrap();
+            assert!(current <= previous);
+            previous = current;
+        }
+    }
+
+    #[test]
+    fn ln_matches_known_values() {
+        assert_eq!(ln(ONE), Some(0));
+        assert_close(ln(2 * ONE).unwrap(), LN_2, 1_000);
+        assert_close(ln(1024 * ONE).unwrap(), 10 * LN_2, 10_000);
+        assert_eq!(ln(ONE - 1), None);
+    }
+
+    #[test]
+    fn lmsr_cost_grows_with_every_quantity() {
+        let liquidity = 100;
+        let mut previous = lmsr_cost(&[0, 0, 0], liquidity).unwrap();
+        for supplies in (10..=1_000).step_by(10) {
+            let current = lmsr_cost(&[supplies, 0, 0], liquidity).unwrap();
+            assert!(current > previous);
+            previous = current;
+        }
+
+        let base = lmsr_cost(&[50, 20], liquidity).unwrap();
+        assert!(lmsr_cost(&[50, 21], liquidity).unwrap() > base);
+        assert!(lmsr_cost(&[51, 20], liquidity).unwrap() > base);
+    }
+
+    #[test]
+    fn lmsr_cost_of_an_empty_market_is_the_max_loss() {
+        for outcomes in 2..=5usize {
+            let quantities = vec![0; outcomes];
+            assert_close(
+                lmsr_cost(&quantities, 100).unwrap(),
+                lmsr_max_loss(outcomes, 100).unwrap(),
+                100_000,
+            );
+        }
+    }
+
+    #[test]
+    fn lmsr_prices_sum_to_one() {
+        for quantities in [
+            vec![0, 0],
+            vec![10, 0],
+            vec![1_000, 3, 0],
+            vec![5, 500, 50, 5_000],
+        ] {
+            let prices = lmsr_prices(&quantities, 100).unwrap();
+            let total: u128 = prices.iter().sum();
+            assert_close(total, ONE, quantities.len() as u128);
+        }
+
+        let prices = lmsr_prices(&[0, 0], 100).unwrap();
+        assert_close(prices[0], ONE / 2, 1);
+        let prices = lmsr_prices(&[100, 0], 100).unwrap();
+        assert!(prices[0] > prices[1]);
+    }
+
+    #[test]
+    fn lmsr_maker_loses_at_most_b_ln_n() {
+        let liquidity = 100;
+        for outcomes in 2..=4usize {
+            let empty = vec![0; outcomes];
+            let max_loss = lmsr_max_loss(outcomes, liquidity).unwrap();
+            for supplies in [1, 50, 1_000, 100_000] {
+                let mut quantities = empty.clone();
+                quantities[0] = supplies;
+
+                // the maker collects the cost difference and pays one unit per winning supply
+                let collected = lmsr_cost(&quantities, liquidity).unwrap()
+                    - lmsr_cost(&empty, liquidity).unwrap();
+                let paid = supplies as u128 * ONE;
+                assert!(paid <= collected + max_loss + 100_000);
+            }
+        }
+    }
+
+    #[test]
+    fn lmsr_rejects_zero_liquidity() {
+        assert_eq!(lmsr_cost(&[1, 2], 0), None);
+        assert_eq!(lmsr_prices(&[1, 2], 0), None);
+        assert_eq!(lmsr_cost(&[], 10), None);
+    }
+}
@@ -0,0 +1,35 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+use ink::prelude::vec::Vec;
+
+// Same trait and message names as the prediction market's `Oracle`, so the selectors match.
+#[ink::trait_definition]
+pub trait Oracle {
+    #[ink(message)]
+    fn get_answer(&self, query: Vec<u8>) -> Option<u128>;
+}
+
+// An oracle that answers every query with the value it was deployed with, used by the
+// prediction market's e2e tests.
+#[ink::contract]
+mod mock_oracle {
+    use ink::prelude::vec::Vec;
+
+    #[ink(storage)]
+    pub struct MockOracle {
+        answer: Option<u128>,
+    }
+
+    impl MockOracle {
+        #[ink(constructor)]
+        pub fn new(answer: Option<u128>) -> Self {
+            Self { answer }
+        }
+    }
+
+    impl super::Oracle for MockOracle {
+        #[ink(message)]
+        fn get_answer(&self, _query: Vec<u8>) -> Option<u128> {
+            self.answer
+        }
+    }
+}
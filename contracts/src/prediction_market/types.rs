@@ -1,9 +1,18 @@
 use ink::prelude::string::String;
+use ink::prelude::vec::Vec;
 use ink::primitives::AccountId;
 
 pub type OutComeId = u64;
 pub type InvestmentFundId = u64;
 pub type TradeId = u64;
+pub type OrderId = u64;
+pub type GovernanceId = u64;
+// (fund, owner, index)
+pub type CheckpointKey = (InvestmentFundId, AccountId, u32);
+// (owner, operator, outcome); no outcome approves all of them
+pub type OutcomeAllowanceKey = (AccountId, AccountId, Option<OutComeId>);
+// (fund, owner, spender)
+pub type ShareAllowanceKey = (InvestmentFundId, AccountId, AccountId);
 pub type EventId = u64;
 pub type Share = u64;
 pub type Supply = u64;
@@ -11,11 +20,25 @@ pub type Balance = u128;
 pub type Timestamp = u64;
 pub type Hash = [u8; 32];
 pub type BlockNumber = u32;
+pub type Probability = u128;
+pub type OracleAnswer = u128;
 
 pub const MIN_EVENT_DEPOSIT: Balance = 1_000_000_000_000;
 pub const MIN_FUND_DEPOSIT: Balance = 1_000_000_000_000;
 pub const MIN_FUND_SHARE: Share = 100;
 pub const DEFAULT_DURATION: Timestamp = 2592000000; // 30days
+pub const MIN_RESOLUTION_BOND: Balance = 1_000_000_000_000;
+pub const DEFAULT_DISPUTE_WINDOW: Timestamp = 86400000; // 1day
+pub const COMMITTEE_TIMEOUT: Timestamp = 604800000; // 7days
+pub const GOVERNANCE_VOTING_PERIOD: Timestamp = 259200000; // 3days
+pub const GOVERNANCE_QUORUM: Share = 30; // percent of total shares
+pub const BASIS_POINTS: u32 = 10_000;
+pub const MAX_MANAGEMENT_FEE: u32 = 500; // 5% a year
+pub const MAX_PERFORMANCE_FEE: u32 = 3_000; // 30% of gains
+pub const YEAR: Timestamp = 31536000000; // 365days
+pub const PROBABILITY_ONE: Probability = 1_000_000_000_000_000_000;
+pub const MAX_BOOK_ORDERS: u32 = 100; // resting orders per side of a fund's book
+pub const MIN_ORDER_VALUE: Balance = 1_000_000_000; // shares * price_per_share
 
 pub struct MyEnvironment;
 
@@ -74,9 +97,88 @@ pub struct OutComePayload {
 pub struct EventMarket {
     pub event_id: EventId,
     pub pool: Balance,
-    pub is_resolved: bool,
+    pub creation_deposit: Balance,
+    pub status: MarketStatus,
     pub resolve_date: Timestamp,
     pub winning_outcome: Option<OutComeId>,
+    pub prize_per_supply: Balance,
+    pub pricing: PricingMode,
+    // PSP22 token the market is denominated in, `None` for the native token
+    pub collateral: Option<AccountId>,
+    pub scalar: Option<ScalarMarket>,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct MarketTerms {
+    pub pricing: PricingMode,
+    pub collateral: Option<AccountId>,
+    // taken from the creator's credit for a token; native markets use the attached value
+    pub deposit: Balance,
+}
+
+// LONG wins the share of the pool the resolved value covers from `lower` up, SHORT the rest
+#[derive(scale::Decode, scale::Encode, Clone, Copy)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct ScalarMarket {
+    pub lower: OracleAnswer,
+    pub upper: OracleAnswer,
+    pub long_outcome: OutComeId,
+    pub short_outcome: OutComeId,
+    pub resolved_value: Option<OracleAnswer>,
+    pub long_prize_per_supply: Balance,
+    pub short_prize_per_supply: Balance,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum MarketStatus {
+    Open,
+    // A winner was proposed with a bond and can be disputed until the window closes.
+    Proposed,
+    // The proposal was counter-bonded and waits for the arbitrator.
+    Disputed,
+    // Finalized, payouts have run.
+    Resolved,
+    // Cancelled without a winner, stakes are refundable at cost basis.
+    Invalid,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum PricingMode {
+    // Every supply costs the outcome's `deposit_per_supply`, winners split the pool.
+    Fixed,
+    // Logarithmic market scoring rule. `liquidity` is `b` in supplies and every winning
+    // supply pays `payout_per_supply`; the creation deposit must cover `b * ln(outcomes)`.
+    Lmsr {
+        liquidity: Supply,
+        payout_per_supply: Balance,
+    },
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct BetQuote {
+    pub outcome_id: OutComeId,
+    pub supplies: Supply,
+    pub cost: Balance,
+    pub probabilities: Vec<(OutComeId, Probability)>,
 }
 
 #[derive(scale::Decode, scale::Encode)]
@@ -90,6 +192,16 @@ pub struct MarketOutCome {
     pub available_supply: Supply,
 }
 
+#[derive(scale::Decode, scale::Encode, Default)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Position {
+    pub supply: Supply,
+    pub cost_basis: Balance,
+}
+
 #[derive(scale::Decode, scale::Encode)]
 #[cfg_attr(
     feature = "std",
@@ -101,6 +213,99 @@ pub struct InvestmentFund {
     pub total_share: Share,
     pub total_fund: Balance,
     pub trader: AccountId,
+    pub subscription: SubscriptionConfig,
+    pub fees: FundFees,
+    pub is_dissolved: bool,
+    pub distribution: DistributionPolicy,
+    // cumulative dividends paid per share, scaled by 1e18
+    pub dividend_per_share: Balance,
+    pub fees_accrued_at: Timestamp,
+    // highest net asset value per share fees have been charged on
+    pub high_water_mark: Balance,
+    // PSP22 token the fund is denominated in, `None` for the native token
+    pub collateral: Option<AccountId>,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum DistributionPolicy {
+    #[default]
+    Reinvest,
+    Distribute,
+}
+
+#[derive(scale::Decode, scale::Encode, Default)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct DividendAccount {
+    pub paid_per_share: Balance,
+    pub pending: Balance,
+}
+
+// Fee rates in basis points; the management fee is annualized.
+#[derive(scale::Decode, scale::Encode, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct FundFees {
+    pub management_fee: u32,
+    pub performance_fee: u32,
+    pub payout: FeePayout,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum FeePayout {
+    #[default]
+    MintShares,
+    PayTrader,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct SubscriptionConfig {
+    pub is_open: bool,
+    pub share_cap: Option<Share>,
+    pub min_ticket: Balance,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct FundValuation {
+    pub investment_fund_id: InvestmentFundId,
+    pub liquid_balance: Balance,
+    pub positions: Vec<PositionValuation>,
+    pub net_asset_value: Balance,
+    pub nav_per_share: Balance,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct PositionValuation {
+    pub outcome_id: OutComeId,
+    pub supply: Supply,
+    pub cost_basis: Balance,
+    pub mark_price: Balance,
+    pub market_value: Balance,
+    pub unrealized_pnl: i128,
 }
 
 #[derive(scale::Decode, scale::Encode)]
@@ -124,7 +329,229 @@ pub struct FundTrade {
     pub proponent: AccountId,
     pub proposed_person: Option<AccountId>,
     pub share: Share,
-    pub price: Balance,
+    pub remaining_share: Share,
+    pub price_per_share: Balance,
+    pub min_fill: Share,
+    // fills live in `trade_fills` under (trade_id, index)
+    pub fill_count: u32,
+    pub auction: Option<AuctionMode>,
+    pub best_bid: Option<TradeBid>,
+    pub opened_at: Timestamp,
     pub close_time: Timestamp,
     pub is_completed: bool,
+    pub is_cancelled: bool,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum AuctionMode {
+    // Bids must beat the best one by `min_increment`; a bid within `extension` of the close
+    // pushes the close out to `extension` after the bid.
+    English {
+        min_increment: Balance,
+        extension: Timestamp,
+    },
+    // The price falls linearly from the starting price to `floor_price` at the close.
+    Dutch {
+        floor_price: Balance,
+    },
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct TradeBid {
+    pub bidder: AccountId,
+    pub price_per_share: Balance,
+    pub placed_at: Timestamp,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct TradeFill {
+    pub buyer: AccountId,
+    pub share: Share,
+    pub price: Balance,
+    pub filled_at: Timestamp,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum TradeStatus {
+    Open,
+    Completed,
+    Cancelled,
+    Expired,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum OrderSide {
+    Bid,
+    Ask,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Order {
+    pub order_id: OrderId,
+    pub investment_fund_id: InvestmentFundId,
+    pub owner: AccountId,
+    pub side: OrderSide,
+    pub price_per_share: Balance,
+    pub share: Share,
+    pub remaining_share: Share,
+    pub placed_at: Timestamp,
+    pub is_cancelled: bool,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct OrderBookLevel {
+    pub price_per_share: Balance,
+    pub share: Share,
+    pub orders: u32,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct OrderBook {
+    pub investment_fund_id: InvestmentFundId,
+    pub bids: Vec<OrderBookLevel>,
+    pub asks: Vec<OrderBookLevel>,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct ResolutionProposal {
+    pub event_id: EventId,
+    pub proposer: AccountId,
+    pub outcome_id: OutComeId,
+    pub bond: Balance,
+    pub proposed_at: Timestamp,
+    pub dispute_deadline: Timestamp,
+    pub disputer: Option<AccountId>,
+    pub dispute_bond: Balance,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum ResolverPayload {
+    Oracle(OracleBindingPayload),
+    Committee(Committee),
+    Scalar(OracleBindingPayload),
+}
+
+// `answers` pairs the lowest oracle answer of a band with the index of the outcome in
+// `create_event`'s `bets`; the band with the greatest threshold not above the answer wins.
+// A scalar event instead pins the answer each of its two outcomes pays in full at: the
+// higher one is LONG, the lower one SHORT, and payouts are linear in between.
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct OracleBindingPayload {
+    pub oracle: AccountId,
+    pub query: Vec<u8>,
+    pub answers: Vec<(OracleAnswer, u32)>,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct OracleBinding {
+    pub oracle: AccountId,
+    pub query: Vec<u8>,
+    pub answers: Vec<(OracleAnswer, OutComeId)>,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Committee {
+    pub members: Vec<AccountId>,
+    pub threshold: u32,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Checkpoint {
+    pub block: BlockNumber,
+    pub share: Share,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum GovernanceAction {
+    ReplaceTrader(AccountId),
+    ChangeFee(FundFees),
+    Dissolve,
+}
+
+#[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum GovernanceStatus {
+    Active,
+    Executed,
+    Rejected,
+}
+
+#[derive(scale::Decode, scale::Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct GovernanceProposal {
+    pub proposal_id: GovernanceId,
+    pub investment_fund_id: InvestmentFundId,
+    pub proposer: AccountId,
+    pub action: GovernanceAction,
+    pub snapshot_block: BlockNumber,
+    pub end_time: Timestamp,
+    pub votes_for: Share,
+    pub votes_against: Share,
+    pub status: GovernanceStatus,
 }